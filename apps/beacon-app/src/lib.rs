@@ -0,0 +1,41 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Building health and status beacons
+//
+// beacon-app's threads gather telemetry and hand it to the radios (see main.rs). Everything in
+// between, turning what they gathered into the bytes of each beacon, lives here so that the
+// ground tools' tests can build beacons exactly the same way:
+//
+// - `snapshot` - The latest values from the telemetry database
+// - `lookup` - Values for the fields which don't come straight from the telemetry database
+
+pub mod lookup;
+pub mod snapshot;
+
+use beacon_schema::{Field, Packet, Value};
+
+// Build a complete beacon frame (the header byte, followed by the packet data) from a packet's
+// definition (see beacon-schema). `lookup` supplies the value of each field, or `None` if it
+// isn't available
+pub fn frame<F>(packet: &Packet, lookup: F) -> Vec<u8>
+where
+    F: FnMut(&Field) -> Option<Value>,
+{
+    let mut frame = vec![packet.header()];
+    frame.extend(packet.encode(lookup));
+    frame
+}
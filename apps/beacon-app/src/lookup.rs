@@ -0,0 +1,222 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Values for the beacon fields which don't come straight from the telemetry database
+//
+// beacon-app gathers the raw inputs for these (log files, `df`, the GPS service and so on), and
+// everything from there on happens here
+
+use crate::snapshot::Snapshot;
+use beacon_schema::{Field, Value, SYSTEM_STATUS_FLAGS};
+use chrono::prelude::*;
+
+// Taken from /proc/meminfo on a BBB
+pub const MEM_TOTAL: f32 = 515_340.0;
+
+// The GPS receiver reports a variable number of status flags, which are stored as
+// `systemStatus_status_0` through `systemStatus_status_23`
+pub const SYSTEM_STATUS_COUNT: usize = 24;
+
+// `TEMPERATURE`: Old readings from the BIM's sensors don't mean anything once they're turned off
+pub fn temperature(snapshot: &Snapshot, bim_sensors: bool, field: &Field) -> Option<Value> {
+    match field.source.parameter() {
+        Some(("bim", _)) if !bim_sensors => None,
+        _ => snapshot.value(field),
+    }
+}
+
+// `GPS_MISC`: Whether the OEM6 service thinks the receiver is powered, and the receiver's
+// combined status flags, plus its telemetry
+pub fn gps_misc(snapshot: &Snapshot, power: Option<u8>, field: &Field) -> Option<Value> {
+    match field.name {
+        "system_status" => Some(Value::Number(f64::from(system_status(snapshot)))),
+        "power" => power.map(|power| Value::Number(f64::from(power))),
+        _ => snapshot.value(field),
+    }
+}
+
+// The telemetry database parameters holding the GPS receiver's status flags
+pub fn system_status_parameters() -> Vec<(String, String)> {
+    (0..SYSTEM_STATUS_COUNT)
+        .map(|num| ("OEM".to_owned(), format!("systemStatus_status_{}", num)))
+        .collect()
+}
+
+// Combine all of the GPS receiver's current status flags
+pub fn system_status(snapshot: &Snapshot) -> u32 {
+    let flag = |num: usize| {
+        snapshot
+            .get("OEM", &format!("systemStatus_status_{}", num))
+            .map(|latest| (latest.timestamp, convert_system_status(&latest.value)))
+            .unwrap_or((0.0, 0))
+    };
+
+    let (benchmark, mut flags) = flag(0);
+
+    for num in 1..SYSTEM_STATUS_COUNT {
+        let (timestamp, flag) = flag(num);
+
+        // We'll have a variable number of flags present, so we need to determine which ones are
+        // only from the latest set of data.
+        // - It should take less than two seconds to store all flags in the database
+        // - telem-app stores every flag from one query with the same timestamp, so a difference
+        //   of zero is the normal case
+        // - If a flag index doesn't exist, its timestamp will be zero, resulting in a negative
+        //   difference
+        let diff = timestamp - benchmark;
+        if !(0.0..=2.0).contains(&diff) {
+            continue;
+        }
+
+        flags |= flag;
+    }
+
+    flags
+}
+
+fn convert_system_status(raw: &str) -> u32 {
+    SYSTEM_STATUS_FLAGS
+        .iter()
+        .find(|(name, _)| *name == raw)
+        .map(|(_, flag)| *flag)
+        .unwrap_or(0)
+}
+
+// `APP_ERRORS` and `SERVICE_ERRORS`: The number of errors in a log file, and the last one.
+// If there are no error messages, the timestamp, source and message are left empty
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ErrorLog {
+    pub count: Option<u16>,
+    // UTC time (time since Unix Epoch in seconds), shrunk to a u16 to fit in the message
+    pub timestamp: u16,
+    pub source: String,
+    pub message: String,
+}
+
+impl ErrorLog {
+    // Read the output of `wc -l` and `tail -n 1` for the log file.
+    //
+    // Log lines look like:
+    // 2019-04-01T16:00:00.123456+00:00 kubos beacon-app[512]:<warn> Failed to send beacon
+    pub fn parse(line_count: &[u8], last_line: &[u8]) -> ErrorLog {
+        // The total number of lines in the error file is our estimate of the current error count
+        let count = line_count
+            .iter()
+            .filter(|elem| elem.is_ascii_digit())
+            .map(|&elem| elem as char)
+            .collect::<String>()
+            .parse()
+            .ok();
+
+        let last_line = std::str::from_utf8(last_line).unwrap_or("");
+        let mut pieces = last_line.split('>');
+        let mut header = pieces.next().unwrap_or("").split(' ');
+
+        let timestamp = header
+            .next()
+            .and_then(|raw| {
+                raw.parse::<DateTime<Utc>>()
+                    .map(|value| value.timestamp())
+                    .ok()
+            })
+            .unwrap_or(0) as u16;
+
+        let _kubos = header.next();
+
+        // The message source. It gets truncated to fit the message
+        let source = header.next().unwrap_or("").to_owned();
+
+        // The actual error message, minus the leading space. It also gets truncated
+        let message = pieces
+            .next()
+            .map(|text| text.trim_start().trim_end_matches('\n'))
+            .unwrap_or("")
+            .to_owned();
+
+        ErrorLog {
+            count,
+            timestamp,
+            source,
+            message,
+        }
+    }
+
+    // Both packets have the same layout, with their fields prefixed by "app_" or "service_"
+    pub fn value(&self, field: &Field) -> Option<Value> {
+        let name = field.name;
+        if name.ends_with("_errors_count") {
+            self.count.map(|count| Value::Number(f64::from(count)))
+        } else if name.ends_with("_last_timestamp") {
+            Some(Value::Number(f64::from(self.timestamp)))
+        } else if name.ends_with("_last_source") {
+            Some(Value::Text(self.source.clone()))
+        } else if name.ends_with("_last_msg") {
+            Some(Value::Text(self.message.clone()))
+        } else {
+            None
+        }
+    }
+}
+
+// `OBC`: RAM and storage space, and whether we've deployed
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObcStatus {
+    pub ram_available: f32,
+    pub disk_in_use: u8,
+    pub deployed: bool,
+}
+
+impl ObcStatus {
+    pub fn value(&self, field: &Field) -> Option<Value> {
+        let value = match field.name {
+            "ram_available" => f64::from(self.ram_available),
+            "disk_in_use" => f64::from(self.disk_in_use),
+            "deployed" => f64::from(self.deployed as u8),
+            _ => return None,
+        };
+        Some(Value::Number(value))
+    }
+}
+
+// Convert the available memory (kB, from /proc/meminfo) to a percentage, since that's a smaller
+// number and basically what we care about anyways
+pub fn ram_percent(memory_available: &str) -> f32 {
+    let mem: f32 = memory_available.parse().unwrap_or(MEM_TOTAL);
+    (mem / MEM_TOTAL) * 100.0
+}
+
+// Get the percent of a partition that's in use from the output of `df`:
+//
+// Filesystem     1K-blocks  Used Available Use% Mounted on
+// /dev/mmcblk0p4   3057704  6760   2875612   1% /home
+//
+// Anything we can't read counts as full
+pub fn disk_percent(df_output: &[u8]) -> u8 {
+    let mut slices = df_output.rsplit(|&elem| elem == b' ');
+
+    // The last entry is the mount point (/home)
+    slices.next();
+    // The second to last entry is the percent in use
+    slices
+        .next()
+        .unwrap_or(&[])
+        .iter()
+        .filter(|elem| elem.is_ascii_digit())
+        .map(|&elem| elem as char)
+        .collect::<String>()
+        .parse()
+        .unwrap_or(100)
+}
@@ -24,7 +24,7 @@
 // - SupMCU
 // - GPS
 // - ADCS
-// - Radio (duplex)
+// - Radio (duplex and simplex health)
 
 mod packets;
 mod transmit;
@@ -57,6 +57,7 @@ impl AppHandler for MyApp {
         let radios = Radios {
             telem_service,
             simplex: Arc::new(Mutex::new(sup_mcu)),
+            duplex: Arc::new(Duplex::new()),
         };
 
         // Spawn threads for each of the beacon messages
@@ -108,7 +109,10 @@ impl AppHandler for MyApp {
         handles.push(handle);
         thread::sleep(THREAD_INTERVAL);
 
-        // TODO: Radio (duplex) packet
+        let radio_radios = radios.clone();
+        let handle = thread::spawn(move || radio::radio_packet(radio_radios));
+        debug!("Spawning radio beacon thread: {:?}", handle.thread().id());
+        handles.push(handle);
 
         // Wait indefinitely for all the threads to exit (which they shouldn't do unless something
         // goes wrong)
@@ -24,16 +24,65 @@ pub mod supmcu;
 pub mod temperature;
 
 use crate::transmit::*;
+use beacon_app::frame;
+use beacon_app::snapshot::{parameters, request, Snapshot};
+use beacon_schema::{Field, Packet, Value};
 use kubos_app::query;
+use log::*;
 use std::time::Duration;
 
-// Common function for reading an entry from the telemetry database
-fn get_string(radios: &Radios, msg: &str) -> String {
-    match query(&radios.telem_service, msg, Some(Duration::from_millis(100))) {
-        Ok(data) => {
-            let value = data["telemetry"][0]["value"].as_str().unwrap_or("");
-            value.to_owned()
+// All of a snapshot's parameters are fetched in a single request, which takes longer than a
+// single value. Allow a little extra time for each parameter, so that even the biggest snapshots
+// (ex. all three power packets) can reliably finish
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(1);
+const SNAPSHOT_TIMEOUT_PER_PARAMETER: Duration = Duration::from_millis(20);
+// A failed request is tried this many more times before the snapshot is given up on
+const SNAPSHOT_RETRIES: usize = 1;
+
+// Fetch the latest entry for each of the given (subsystem, parameter) pairs from the telemetry
+// database, all in one request (see `beacon_app::snapshot`).
+// Parameters without any entries are left out. If the request fails, there's no snapshot at all:
+// the beacon would otherwise be built from nothing but default values
+pub fn fetch_latest<S, P>(radios: &Radios, parameters: &[(S, P)]) -> Option<Snapshot>
+where
+    S: AsRef<str>,
+    P: AsRef<str>,
+{
+    let request = request(parameters);
+    let timeout = SNAPSHOT_TIMEOUT + SNAPSHOT_TIMEOUT_PER_PARAMETER * parameters.len() as u32;
+
+    for attempt in 0..=SNAPSHOT_RETRIES {
+        match query(&radios.telem_service, &request, Some(timeout)) {
+            Ok(data) => return Some(Snapshot::from_response(parameters, &data)),
+            Err(error) if attempt < SNAPSHOT_RETRIES => {
+                warn!("Failed to fetch latest telemetry, retrying: {:?}", error);
+            }
+            Err(error) => error!("Failed to fetch latest telemetry: {:?}", error),
         }
-        Err(_) => "".to_owned(),
     }
+
+    None
+}
+
+// Fetch everything the given packets need from the telemetry database, all at once, so that the
+// packets are built from one consistent snapshot.
+// If it can't be fetched, the packets are skipped until their next cycle
+fn snapshot(radios: &Radios, packets: &[&Packet]) -> Option<Snapshot> {
+    let snapshot = fetch_latest(radios, &parameters(packets));
+    if snapshot.is_none() {
+        warn!(
+            "Skipping beacons {:?}: No telemetry snapshot",
+            packets.iter().map(|packet| packet.name).collect::<Vec<_>>()
+        );
+    }
+    snapshot
+}
+
+// Build a packet from its definition (see beacon-schema) and send it.
+// `lookup` supplies the value of each field, or `None` if it isn't available
+fn send<F>(radios: &Radios, packet: &Packet, lookup: F)
+where
+    F: FnMut(&Field) -> Option<Value>,
+{
+    radios.transmit(&frame(packet, lookup));
 }
@@ -16,60 +16,42 @@
 
 // Gather RAM and storage space information every hour
 //
-// Message layout:
-// 0: % RAM available
-// 1: % user data partition in use (/home)
-// 2: Deployment status (0 = not deployed, 1 = deployed)
-// 3-33: free
+// See `OBC` in beacon-schema for the message layout
 
+use super::{fetch_latest, send};
 use crate::transmit::*;
-use kubos_app::query;
+use beacon_app::lookup::{disk_percent, ram_percent, ObcStatus};
+use beacon_schema::OBC;
 use kubos_system::UBootVars;
 use log::*;
 use std::process::Command;
 use std::thread;
 use std::time::Duration;
 
-const STORAGE_QUERY: &str = r#"{
-    telemetry(subsystem: "OBC", parameter: "memory_available", limit: 1) {
-        timestamp,
-        value
-    }
-}"#;
-
-// Taken from /proc/meminfo on a BBB
-const MEM_TOTAL: f32 = 515_340.0;
-
 pub fn obc_packet(radios: Radios) {
-    let mut last_timestamp: String = "".to_string();
+    let mut last_timestamp: Option<f64> = None;
 
     loop {
-        // Get last known memory values from telem db
-        let ram_percent = match query(
-            &radios.telem_service,
-            STORAGE_QUERY,
-            Some(Duration::from_millis(100)),
-        ) {
-            Ok(data) => {
-                let mem: f32 = data["telemetry"][0]["value"]
-                    .as_str()
-                    .and_then(|val| val.parse().ok())
-                    .unwrap_or(MEM_TOTAL);
-
+        // Get last known memory values from telem db.
+        // The rest of the packet doesn't come from there, so it's still sent if this can't be
+        // fetched
+        let snapshot = fetch_latest(&radios, &[("OBC", "memory_available")]);
+        let latest = snapshot
+            .as_ref()
+            .and_then(|snapshot| snapshot.get("OBC", "memory_available"));
+        let ram_available = match latest {
+            Some(latest) => {
                 // Verify that this is a new value, not a repeat from the last time we asked
-                let timestamp = data["telemetry"][0]["timestamp"].as_str().unwrap_or("");
-                if timestamp == last_timestamp {
+                if last_timestamp == Some(latest.timestamp) {
                     error!("Available memory timestamp has not changed");
                 } else {
-                    last_timestamp = timestamp.to_string();
+                    last_timestamp = Some(latest.timestamp);
                 }
 
-                // Convert to percentage, since that's a smaller number and basically what we care
-                // about anyways
-                (mem / MEM_TOTAL) * 100.0
+                ram_percent(&latest.value)
             }
-            Err(error) => {
-                error!("Unable to get last known memory usage: {:?}", error);
+            None => {
+                error!("Unable to get last known memory usage");
                 0.0
             }
         };
@@ -81,7 +63,7 @@ pub fn obc_packet(radios: Radios) {
         //
         // Note: I tried to just use a wildcard ("/dev/mmcblk*p4"), but couldn't get the correct
         // output for some reason, so we're doing this the long way.
-        let disk_percent = if let Ok(output1) = Command::new("df").arg("/dev/mmcblk1p4").output() {
+        let disk_in_use = if let Ok(output1) = Command::new("df").arg("/dev/mmcblk1p4").output() {
             let stdout = if output1.stderr.is_empty() {
                 output1.stdout
             } else if let Ok(output0) = Command::new("df").arg("/dev/mmcblk0p4").output() {
@@ -94,26 +76,7 @@ pub fn obc_packet(radios: Radios) {
                 vec![]
             };
 
-            let mut slices = stdout.rsplit(|&elem| elem == b' ');
-
-            // The last entry is the mount point (/home)
-            slices.next();
-            // The second to last entry is the percent in use
-            let temp = slices.next();
-            // Convert it to a useable number
-            let percent = temp
-                .unwrap_or(&[])
-                .iter()
-                .filter_map(|&elem| {
-                    if elem.is_ascii_digit() {
-                        Some(elem as char)
-                    } else {
-                        None
-                    }
-                })
-                .collect::<String>();
-
-            percent.parse::<u8>().unwrap_or(100)
+            disk_percent(&stdout)
         } else {
             error!("Failed to get current disk usage info");
             100
@@ -121,10 +84,12 @@ pub fn obc_packet(radios: Radios) {
 
         let deployed = UBootVars::new().get_bool("deployed").unwrap_or(false);
 
-        // Turn into data packet
-        let msg: [u8; 3] = [ram_percent as u8, disk_percent, deployed as u8];
-
-        let _ = radios.transmit(MessageType::OBC, 0, &msg);
+        let status = ObcStatus {
+            ram_available,
+            disk_in_use,
+            deployed,
+        };
+        send(&radios, &OBC, |field| status.value(field));
 
         thread::sleep(Duration::from_secs(3600));
     }
@@ -14,9 +14,23 @@
 // limitations under the License.
 //
 
-/*
-Radio (duplex) @ 1hr
-- Power state
-- # commands (packet receive/sent counters)
-- Other useful duplex telemetry?
-*/
+// Gather duplex radio health every hour
+//
+// See `RADIO` in beacon-schema for the message layout
+
+use super::{send, snapshot};
+use crate::transmit::*;
+use beacon_schema::RADIO;
+use std::thread;
+use std::time::Duration;
+
+pub fn radio_packet(radios: Radios) {
+    loop {
+        if let Some(snapshot) = snapshot(&radios, &[&RADIO]) {
+            send(&radios, &RADIO, |field| snapshot.value(field));
+        }
+
+        // Run every hour
+        thread::sleep(Duration::from_secs(3600));
+    }
+}
@@ -0,0 +1,124 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// The latest telemetry database values for a set of parameters
+//
+// All of the parameters a beacon needs are fetched at the same time, in a single request with one
+// alias per parameter (see `request`), so that the beacon is built from one consistent snapshot.
+
+use beacon_schema::{Field, Packet, Value};
+use std::collections::HashMap;
+
+// The latest entry for a telemetry database parameter
+#[derive(Clone, Debug)]
+pub struct Latest {
+    pub value: String,
+    pub timestamp: f64,
+}
+
+// The latest entries for a set of telemetry database parameters, all fetched at the same time
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    entries: HashMap<(String, String), Latest>,
+}
+
+impl Snapshot {
+    // Read the response to a `request` for the same parameters.
+    // Parameters without any entries are left out
+    pub fn from_response<S, P>(parameters: &[(S, P)], data: &serde_json::Value) -> Snapshot
+    where
+        S: AsRef<str>,
+        P: AsRef<str>,
+    {
+        let mut snapshot = Snapshot::default();
+
+        for (index, (subsystem, parameter)) in parameters.iter().enumerate() {
+            let entry = &data[format!("p{}", index).as_str()][0];
+            let value = match entry["value"].as_str() {
+                Some(value) => value.to_owned(),
+                None => continue,
+            };
+            let timestamp = entry["timestamp"]
+                .as_f64()
+                .or_else(|| entry["timestamp"].as_str().and_then(|val| val.parse().ok()))
+                .unwrap_or(0.0);
+
+            snapshot.insert(
+                subsystem.as_ref(),
+                parameter.as_ref(),
+                Latest { value, timestamp },
+            );
+        }
+
+        snapshot
+    }
+
+    pub fn insert(&mut self, subsystem: &str, parameter: &str, latest: Latest) {
+        self.entries
+            .insert((subsystem.to_owned(), parameter.to_owned()), latest);
+    }
+
+    pub fn get(&self, subsystem: &str, parameter: &str) -> Option<&Latest> {
+        self.entries
+            .get(&(subsystem.to_owned(), parameter.to_owned()))
+    }
+
+    // The value of a field which comes from the telemetry database
+    pub fn value(&self, field: &Field) -> Option<Value> {
+        let (subsystem, parameter) = field.source.parameter()?;
+        field.parse(&self.get(subsystem, parameter)?.value)
+    }
+}
+
+// Build the request for the latest entry of each of the given (subsystem, parameter) pairs.
+// Each pair gets its own alias in the query:
+//
+// {
+//     p0: telemetry(subsystem: "bm2", parameter: "voltage", limit: 1) { timestamp, value },
+//     p1: ...
+// }
+pub fn request<S, P>(parameters: &[(S, P)]) -> String
+where
+    S: AsRef<str>,
+    P: AsRef<str>,
+{
+    let aliases: Vec<String> = parameters
+        .iter()
+        .enumerate()
+        .map(|(index, (subsystem, parameter))| {
+            format!(
+                r#"p{}: telemetry(subsystem: "{}", parameter: "{}", limit: 1) {{
+                    timestamp,
+                    value
+                }}"#,
+                index,
+                subsystem.as_ref(),
+                parameter.as_ref()
+            )
+        })
+        .collect();
+
+    format!("{{\n{}\n}}", aliases.join(",\n"))
+}
+
+// The telemetry database parameters used by the given packets
+pub fn parameters(packets: &[&Packet]) -> Vec<(&'static str, &'static str)> {
+    packets
+        .iter()
+        .flat_map(|packet| packet.fields.iter())
+        .filter_map(|field| field.source.parameter())
+        .collect()
+}
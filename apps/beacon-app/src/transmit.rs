@@ -17,10 +17,25 @@
 // Module for actually sending messages
 //
 // All messages are sent over both the simplex and the duplex
+//
+// Duplex messages are handed to the NSL duplex comms service through its downlink port. The comms
+// service wraps each one in a UDP-type SpacePacket (the same framing the duplex gateway parses)
+// and passes it to the radio. While the duplex isn't responding, messages are queued and then
+// sent, oldest first, once it's back.
+//
+// The outcome of each send is logged. Beacons are sent again on their next cycle no matter what,
+// so there's nothing for the callers to do about a failure.
+//
+// [beacon-app]
+// # Maximum number of messages to hold while the duplex is unavailable
+// duplex_queue = 50
 
-use failure::{bail, Error};
+use beacon_schema::MAX_PAYLOAD;
+use failure::{bail, format_err, Error};
 use kubos_app::{query, ServiceConfig};
 use log::*;
+use std::collections::VecDeque;
+use std::net::UdpSocket;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
@@ -29,7 +44,104 @@ use std::time::Duration;
 pub struct Radios {
     pub telem_service: ServiceConfig,
     pub simplex: Arc<Mutex<ServiceConfig>>,
-    // TODO: duplex: DuplexD2,
+    pub duplex: Arc<Duplex>,
+}
+
+#[derive(Debug, PartialEq)]
+enum DuplexStatus {
+    // This many messages were sent, oldest first. Zero means another thread got to this one first
+    Sent(usize),
+    // The duplex is unavailable, so the message is waiting in the queue (with this many messages
+    // in total)
+    Queued(usize),
+}
+
+const DEFAULT_DUPLEX_PORT: u16 = 14011;
+const DEFAULT_DUPLEX_QUEUE: usize = 50;
+
+const DUPLEX_ALIVE: &str = r#"{
+    alive
+}"#;
+
+pub struct Duplex {
+    service: ServiceConfig,
+    capacity: usize,
+    // Messages waiting to be sent, oldest first. This is only locked while it's being changed, so
+    // that beacon threads never have to wait on the radio just to queue a message
+    queue: Mutex<VecDeque<Vec<u8>>>,
+    // Held while messages are being handed to the radio, so that they go out in order
+    sending: Mutex<()>,
+}
+
+impl Duplex {
+    pub fn new() -> Duplex {
+        let capacity = ServiceConfig::new("beacon-app")
+            .get("duplex_queue")
+            .and_then(|val| val.as_integer())
+            .map(|val| val.max(1) as usize)
+            .unwrap_or(DEFAULT_DUPLEX_QUEUE);
+
+        Duplex {
+            service: ServiceConfig::new("nsl-duplex-d2-comms-service"),
+            capacity,
+            queue: Mutex::new(VecDeque::new()),
+            sending: Mutex::new(()),
+        }
+    }
+
+    // Add a message to the back of the queue, dropping the oldest one if it's full.
+    // Returns the number of messages now waiting
+    fn enqueue(&self, packet: &[u8]) -> usize {
+        // If the mutex gets poisoned, we want to crash as noisily as possible
+        let mut queue = self.queue.lock().unwrap();
+
+        queue.push_back(packet.to_vec());
+        if queue.len() > self.capacity {
+            queue.pop_front();
+            warn!("Duplex queue full. Dropped oldest beacon");
+        }
+
+        queue.len()
+    }
+
+    // The duplex can only send messages if it's actually responding
+    fn available(&self) -> bool {
+        match query(
+            &self.service,
+            DUPLEX_ALIVE,
+            Some(Duration::from_millis(500)),
+        ) {
+            Ok(data) => data["alive"].as_bool().unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+
+    // Hand a message to the comms service's downlink endpoint
+    fn downlink(&self, packet: &[u8]) -> Result<(), Error> {
+        let comms = self
+            .service
+            .get("comms")
+            .ok_or_else(|| format_err!("Missing duplex comms config"))?;
+        let ip = comms
+            .get("ip")
+            .and_then(|val| val.as_str())
+            .unwrap_or("0.0.0.0")
+            .to_owned();
+        let port = comms
+            .get("downlink_ports")
+            .and_then(|ports| ports.as_array().and_then(|ports| ports.first().cloned()))
+            .and_then(|port| port.as_integer())
+            .map(|port| port as u16)
+            .unwrap_or(DEFAULT_DUPLEX_PORT);
+
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        let sent = socket.send_to(packet, (ip.as_str(), port))?;
+        if sent != packet.len() {
+            bail!("Only sent {} of {} bytes", sent, packet.len());
+        }
+
+        Ok(())
+    }
 }
 
 pub const SIMPLEX_STATUS: &str = r#"{
@@ -37,28 +149,45 @@ pub const SIMPLEX_STATUS: &str = r#"{
 }"#;
 
 impl Radios {
-    pub fn transmit(&self, msg_type: MessageType, subtype: u8, data: &[u8]) -> Result<(), Error> {
-        // Combine message type and subtype into single header byte
-        // 7 6 5 4 3 | 2 1 0
-        //  Msg type | Sub type
-        let header: u8 = ((msg_type as u8) << 3) | subtype;
-
-        if data.len() > 34 {
-            bail!("Message too long");
-        }
+    // Send a full beacon frame (the header byte, followed by the packet data)
+    pub fn transmit(&self, packet: &[u8]) {
+        let header = match packet.first() {
+            Some(header) => *header,
+            None => {
+                error!("Not sending empty beacon");
+                return;
+            }
+        };
 
-        // Create full message packet
-        let mut packet = vec![header];
-        packet.extend_from_slice(data);
+        if packet.len() - 1 > MAX_PAYLOAD {
+            error!("Not sending beacon {:#04x}: Message too long", header);
+            return;
+        }
 
-        // Send the packet
-        if let Err(error) = self.send_simplex(&packet) {
-            error!("Failed to send beacon over simplex: {:?}", error);
+        match self.send_simplex(packet) {
+            Ok(()) => info!("Sent beacon {:#04x} over simplex", header),
+            Err(error) => error!("Failed to send beacon over simplex: {:?}", error),
         }
-        if let Err(error) = self.send_duplex(&packet) {
-            error!("Failed to send beacon over duplex: {:?}", error);
+
+        match self.send_duplex(packet) {
+            Ok(DuplexStatus::Sent(0)) => {
+                debug!(
+                    "Beacon {:#04x} was sent over duplex by another thread",
+                    header
+                )
+            }
+            Ok(DuplexStatus::Sent(1)) => info!("Sent beacon {:#04x} over duplex", header),
+            Ok(DuplexStatus::Sent(count)) => info!(
+                "Sent beacon {:#04x} over duplex, along with {} queued beacons",
+                header,
+                count - 1
+            ),
+            Ok(DuplexStatus::Queued(count)) => warn!(
+                "Duplex unavailable. Queued beacon {:#04x} ({} waiting)",
+                header, count
+            ),
+            Err(error) => error!("Failed to send beacon over duplex: {:?}", error),
         }
-        Ok(())
     }
 
     // Note: This send logic is configured to be sent via the RHM supMCU module.
@@ -153,21 +282,38 @@ impl Radios {
         Ok(status)
     }
 
-    fn send_duplex(&self, packet: &[u8]) -> Result<(), Error> {
-        debug!("Sending packet over duplex: {:?}", packet);
-        Ok(())
-    }
-}
+    fn send_duplex(&self, packet: &[u8]) -> Result<DuplexStatus, Error> {
+        let duplex = &self.duplex;
+        let waiting = duplex.enqueue(packet);
+
+        // Only one thread talks to the radio at a time. Anything queued in the meantime is
+        // picked up by whichever thread is sending
+        // If the mutex gets poisoned, we want to crash as noisily as possible
+        let _sending = duplex.sending.lock().unwrap();
+
+        if !duplex.available() {
+            return Ok(DuplexStatus::Queued(waiting));
+        }
+
+        // Send everything in the order it was created. Anything which fails goes back to the
+        // front of the queue
+        let mut sent = 0;
+        loop {
+            let next = match duplex.queue.lock().unwrap().pop_front() {
+                Some(next) => next,
+                None => break,
+            };
+
+            debug!("Sending packet over duplex: {:#02x?}", next);
+            if let Err(error) = duplex.downlink(&next) {
+                duplex.queue.lock().unwrap().push_front(next);
+                return Err(error);
+            }
+            sent += 1;
+        }
 
-pub enum MessageType {
-    ADCS = 0,
-    Errors = 1,
-    GPS = 2,
-    OBC = 3,
-    Power = 4,
-    Radio = 5,
-    SupMCU = 6,
-    Temperature = 7,
+        Ok(DuplexStatus::Sent(sent))
+    }
 }
 
 // Current status of the simplex, as reported by the RHM supMCU
@@ -0,0 +1,88 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Requests for, and responses to, telemetry database snapshots
+
+use beacon_app::snapshot::{parameters, request, Snapshot};
+use beacon_schema::{Value, GENERAL_POWER, OBC, SUPMCU};
+
+#[test]
+fn lists_database_parameters() {
+    // The OBC packet is built entirely from local values
+    let wanted = parameters(&[&OBC, &SUPMCU]);
+    assert_eq!(wanted.len(), SUPMCU.fields.len());
+    assert_eq!(wanted[0], ("aim2", "time"));
+}
+
+#[test]
+fn requests_everything_at_once() {
+    let wanted = [
+        ("bm2", "voltage"),
+        ("OBC", "memory_available"),
+        ("bim", "temp0"),
+    ];
+    let query = request(&wanted);
+
+    assert!(query
+        .contains(r#"p1: telemetry(subsystem: "OBC", parameter: "memory_available", limit: 1)"#));
+    // One request, with an alias for every parameter
+    assert!(query.starts_with('{') && query.ends_with('}'));
+    assert_eq!(query.matches("telemetry(").count(), wanted.len());
+    assert!(query.contains(r#"p2: telemetry(subsystem: "bim", parameter: "temp0""#));
+}
+
+#[test]
+fn reads_snapshot_responses() {
+    let wanted = [
+        ("bm2", "voltage"),
+        ("OBC", "memory_available"),
+        ("bim", "temp0"),
+    ];
+    let response = serde_json::json!({
+        "p0": [{"timestamp": 1554134400.5, "value": "8243"}],
+        "p1": [{"timestamp": "1554134401", "value": "257670"}],
+        "p2": []
+    });
+    let snapshot = Snapshot::from_response(&wanted, &response);
+
+    assert_eq!(snapshot.get("bm2", "voltage").unwrap().value, "8243");
+    assert_eq!(
+        snapshot.get("OBC", "memory_available").unwrap().timestamp,
+        1554134401.0
+    );
+    assert!(snapshot.get("bim", "temp0").is_none());
+}
+
+#[test]
+fn looks_up_field_values() {
+    let field = GENERAL_POWER
+        .fields
+        .iter()
+        .find(|field| field.source.parameter().is_some())
+        .unwrap();
+    let (subsystem, parameter) = field.source.parameter().unwrap();
+
+    let response = serde_json::json!({
+        "p0": [{"timestamp": 1554134400.0, "value": "1"}]
+    });
+    let snapshot = Snapshot::from_response(&[(subsystem, parameter)], &response);
+    assert_eq!(snapshot.value(field), field.parse("1"));
+    assert!(snapshot.value(field).is_some());
+
+    // Missing parameters have no value, so the packet falls back to its defaults
+    let snapshot = Snapshot::from_response(&[(subsystem, parameter)], &serde_json::json!({}));
+    assert_eq!(snapshot.value(field), None::<Value>);
+}
@@ -0,0 +1,342 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Decode health and status beacons on the ground
+//
+// Beacons arrive either as raw hex payloads (header byte followed by the packet data) or as the
+// JSON records NSL delivers for simplex messages:
+//
+// {"PayloadID":"747959","Payload":"3811105757DE...","DT_NSLReceived":"2017-12-18 22:24:10"}
+//
+// Each one is decoded using the packet definitions in beacon-schema, so anything beacon-app can
+// send, we can read.
+
+use beacon_schema::{MessageType, Packet, Reading, Width, PACKETS};
+use failure::{bail, format_err, Error};
+use serde_json::{json, Map, Value as Json};
+
+// A field exactly as it was sent
+#[derive(Clone, Debug, PartialEq)]
+pub enum Raw {
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl Raw {
+    fn to_json(&self) -> Json {
+        match self {
+            Raw::Unsigned(value) => json!(value),
+            Raw::Signed(value) => json!(value),
+            Raw::Float(value) => json!(value),
+            Raw::Text(value) => json!(value),
+        }
+    }
+}
+
+// A single decoded field
+#[derive(Clone, Debug)]
+pub struct Field {
+    pub name: &'static str,
+    // Already in the units given in the packet's definition (ex. mV, whole degrees C)
+    pub raw: Raw,
+    // The name matching the code sent, for fields which are sent as one of a fixed set of names
+    pub label: Option<&'static str>,
+    // Whether the field holds its default value, which is what's sent when the real value isn't
+    // available. Some defaults (ex. 0) are also perfectly good values, so this is only a hint
+    pub default: bool,
+}
+
+impl Field {
+    fn new(reading: &Reading) -> Field {
+        let field = reading.field;
+        let number = reading.raw.as_f64().unwrap_or(0.0);
+        let raw = match (field.width, &reading.raw) {
+            (_, beacon_schema::Value::Text(text)) => Raw::Text(text.clone()),
+            (Width::U8, _) | (Width::U16, _) | (Width::U32, _) => Raw::Unsigned(number as u64),
+            (Width::I8, _) | (Width::I16, _) | (Width::I32, _) => Raw::Signed(number as i64),
+            _ => Raw::Float(number),
+        };
+
+        Field {
+            name: field.name,
+            raw,
+            label: reading.label(),
+            default: reading.is_default(),
+        }
+    }
+
+    // The most useful form of the field: the name matching its code, or the value itself
+    pub fn to_json(&self) -> Json {
+        match self.label {
+            Some(label) => json!(label),
+            None => self.raw.to_json(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        match self.to_json() {
+            Json::String(text) => text,
+            other => other.to_string(),
+        }
+    }
+}
+
+// A decoded beacon
+#[derive(Clone, Debug)]
+pub struct Beacon {
+    pub header: u8,
+    pub msg_type: MessageType,
+    pub subtype: u8,
+    pub packet: &'static Packet,
+    pub fields: Vec<Field>,
+}
+
+impl Beacon {
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+// Decode a full frame (header byte followed by the packet data)
+pub fn decode(frame: &[u8]) -> Result<Beacon, Error> {
+    let header = *frame.first().ok_or_else(|| format_err!("Empty beacon"))?;
+    let msg_type = MessageType::from_header(header)
+        .ok_or_else(|| format_err!("Unknown message type in header {:#04x}", header))?;
+
+    let (packet, readings) = beacon_schema::decode(frame)?;
+
+    Ok(Beacon {
+        header,
+        msg_type,
+        subtype: beacon_schema::subtype(header),
+        packet,
+        fields: readings.iter().map(Field::new).collect(),
+    })
+}
+
+// Decode a frame given as hex (ex. "1801FF00")
+pub fn decode_hex(hex: &str) -> Result<Beacon, Error> {
+    decode(&from_hex(hex)?)
+}
+
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, Error> {
+    let hex = hex.trim();
+    let hex = hex
+        .strip_prefix("0x")
+        .or_else(|| hex.strip_prefix("0X"))
+        .unwrap_or(hex);
+
+    if hex.len() % 2 == 1 {
+        bail!("Hex payload has an odd number of digits");
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| {
+            hex.get(index..index + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| format_err!("Invalid hex payload: {}", hex))
+        })
+        .collect()
+}
+
+// One beacon, as it arrived on the ground
+#[derive(Clone, Debug, Default)]
+pub struct Record {
+    // NSL's `PayloadID`
+    pub id: Option<String>,
+    // NSL's `DT_NSLReceived`
+    pub received: Option<String>,
+    pub payload: String,
+}
+
+impl Record {
+    pub fn decode(&self) -> Result<Beacon, Error> {
+        decode_hex(&self.payload)
+    }
+}
+
+// Split input into records. The input can be:
+// - An NSL JSON record, or an array of them
+// - NSL JSON records, one per line
+// - Hex payloads, separated by whitespace
+pub fn parse_records(input: &str) -> Result<Vec<Record>, Error> {
+    let trimmed = input.trim();
+    if !trimmed.starts_with('[') && !trimmed.starts_with('{') {
+        return Ok(trimmed
+            .split_whitespace()
+            .map(|payload| Record {
+                payload: payload.to_owned(),
+                ..Default::default()
+            })
+            .collect());
+    }
+
+    let values: Vec<Json> = match serde_json::from_str(trimmed) {
+        Ok(Json::Array(values)) => values,
+        Ok(value) => vec![value],
+        Err(_) => trimmed
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?,
+    };
+
+    values.iter().map(record_from_json).collect()
+}
+
+fn record_from_json(value: &Json) -> Result<Record, Error> {
+    let text = |key: &str| match &value[key] {
+        Json::String(text) => Some(text.clone()),
+        Json::Number(number) => Some(number.to_string()),
+        _ => None,
+    };
+
+    Ok(Record {
+        id: text("PayloadID"),
+        received: text("DT_NSLReceived"),
+        payload: text("Payload").ok_or_else(|| format_err!("Record has no payload: {}", value))?,
+    })
+}
+
+// A record and its decoded fields. Records which can't be decoded get an `error` instead of
+// `fields`
+pub fn to_json(record: &Record, beacon: &Result<Beacon, Error>) -> Json {
+    let mut object = Map::new();
+    if let Some(id) = &record.id {
+        object.insert("id".to_owned(), json!(id));
+    }
+    if let Some(received) = &record.received {
+        object.insert("received".to_owned(), json!(received));
+    }
+    object.insert("payload".to_owned(), json!(record.payload));
+
+    match beacon {
+        Ok(beacon) => {
+            object.insert("header".to_owned(), json!(beacon.header));
+            object.insert("type".to_owned(), json!(format!("{:?}", beacon.msg_type)));
+            object.insert("subtype".to_owned(), json!(beacon.subtype));
+            object.insert("packet".to_owned(), json!(beacon.packet.name));
+            let fields: Map<String, Json> = beacon
+                .fields
+                .iter()
+                .map(|field| (field.name.to_owned(), field.to_json()))
+                .collect();
+            object.insert("fields".to_owned(), Json::Object(fields));
+        }
+        Err(error) => {
+            object.insert("error".to_owned(), json!(error.to_string()));
+        }
+    }
+
+    Json::Object(object)
+}
+
+pub const CSV_HEADER: &str = "id,received,packet,field,value,raw";
+
+// One CSV line per field (see `CSV_HEADER`). Records which can't be decoded get a single line,
+// with the error as the value
+pub fn to_csv(record: &Record, beacon: &Result<Beacon, Error>) -> Vec<String> {
+    let id = record.id.as_deref().unwrap_or("");
+    let received = record.received.as_deref().unwrap_or("");
+
+    match beacon {
+        Ok(beacon) => beacon
+            .fields
+            .iter()
+            .map(|field| {
+                let raw = match field.raw.to_json() {
+                    Json::String(text) => text,
+                    other => other.to_string(),
+                };
+                csv_line(&[
+                    id,
+                    received,
+                    beacon.packet.name,
+                    field.name,
+                    &field.to_text(),
+                    &raw,
+                ])
+            })
+            .collect(),
+        Err(error) => vec![csv_line(&[
+            id,
+            received,
+            "",
+            "error",
+            &error.to_string(),
+            &record.payload,
+        ])],
+    }
+}
+
+fn csv_line(columns: &[&str]) -> String {
+    columns
+        .iter()
+        .map(|column| {
+            if column.contains(&[',', '"', '\n', '\r'][..]) {
+                format!("\"{}\"", column.replace('"', "\"\""))
+            } else {
+                (*column).to_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// The packet definitions, for ground tools which can't use beacon-schema directly (ex. the
+// simplex gateway). Each packet's layout is given as a Python `struct` format string:
+//
+// {"packets": [{"name": "obc", "header": 24, "type": "OBC", "subtype": 0,
+//               "parsing": "<BBB", "names": ["ram_available", "disk_in_use", "deployed"]}, ...]}
+pub fn tables() -> Json {
+    let packets: Vec<Json> = PACKETS
+        .iter()
+        .map(|packet| {
+            let parsing: String = std::iter::once("<".to_owned())
+                .chain(packet.fields.iter().map(|field| struct_format(field.width)))
+                .collect();
+            let names: Vec<&str> = packet.fields.iter().map(|field| field.name).collect();
+
+            json!({
+                "name": packet.name,
+                "header": packet.header(),
+                "type": format!("{:?}", packet.msg_type),
+                "subtype": packet.subtype,
+                "parsing": parsing,
+                "names": names,
+            })
+        })
+        .collect();
+
+    json!({ "packets": packets })
+}
+
+fn struct_format(width: Width) -> String {
+    match width {
+        Width::U8 => "B".to_owned(),
+        Width::I8 => "b".to_owned(),
+        Width::U16 => "H".to_owned(),
+        Width::I16 => "h".to_owned(),
+        Width::U32 => "I".to_owned(),
+        Width::I32 => "i".to_owned(),
+        Width::F32 => "f".to_owned(),
+        Width::F64 => "d".to_owned(),
+        Width::Text(size) => format!("{}s", size),
+    }
+}
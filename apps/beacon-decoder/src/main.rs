@@ -0,0 +1,113 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Decode health and status beacons
+//
+// Usage: beacon-decoder [--csv] [INPUT...]
+//        beacon-decoder --tables
+//
+// Each input is a file, "-" for stdin, or a hex payload/NSL JSON record given directly.
+// With no inputs, stdin is read. Decoded beacons are printed as JSON (one object per line), or as
+// CSV with `--csv`.
+//
+// `--tables` prints the packet definitions instead (see `tables`), which is where the simplex
+// gateway's parsing tables come from.
+
+use beacon_decoder::*;
+use failure::{bail, Error};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process;
+
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("beacon-decoder: {}", error);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let mut csv = false;
+    let mut inputs = vec![];
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--csv" => csv = true,
+            "--json" => csv = false,
+            "--tables" => {
+                println!("{:#}", tables());
+                return Ok(());
+            }
+            "-h" | "--help" => {
+                println!("Usage: beacon-decoder [--csv] [INPUT...]");
+                println!("       beacon-decoder --tables");
+                println!();
+                println!("Each INPUT is a file, \"-\" for stdin, or a hex payload or NSL JSON");
+                println!("record. Reads stdin if no inputs are given.");
+                println!();
+                println!("--tables prints the packet definitions as JSON.");
+                return Ok(());
+            }
+            flag if flag.starts_with("--") => bail!("Unknown option: {}", flag),
+            _ => inputs.push(arg),
+        }
+    }
+
+    if inputs.is_empty() {
+        inputs.push("-".to_owned());
+    }
+
+    let mut records = vec![];
+    for input in inputs {
+        let text = if input == "-" {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            text
+        } else if Path::new(&input).is_file() {
+            fs::read_to_string(&input)?
+        } else {
+            input
+        };
+
+        records.extend(parse_records(&text)?);
+    }
+
+    if csv {
+        println!("{}", CSV_HEADER);
+    }
+
+    let mut failed = 0;
+    for record in records {
+        let beacon = record.decode();
+        if beacon.is_err() {
+            failed += 1;
+        }
+
+        if csv {
+            for line in to_csv(&record, &beacon) {
+                println!("{}", line);
+            }
+        } else {
+            println!("{}", to_json(&record, &beacon));
+        }
+    }
+
+    if failed > 0 {
+        bail!("Failed to decode {} beacon(s)", failed);
+    }
+
+    Ok(())
+}
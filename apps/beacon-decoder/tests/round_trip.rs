@@ -0,0 +1,398 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Build beacons with beacon-app's own library (telemetry database strings run through the
+// packet definitions) and make sure they decode back to the same values
+
+use beacon_app::frame;
+use beacon_app::lookup::{self, disk_percent, ram_percent, ErrorLog, ObcStatus};
+use beacon_app::snapshot::{Latest, Snapshot};
+use beacon_decoder::*;
+use beacon_schema::{Field as SchemaField, Packet, Source, Value, Width, MAX_PAYLOAD, PACKETS};
+
+// A plausible telemetry database value for a field, as the string it would be stored as
+fn sample(field: &SchemaField) -> String {
+    match field.source {
+        Source::Named(_, _, names) => names[1].0.to_owned(),
+        Source::Hex(..) => "1F".to_owned(),
+        Source::Flag(..) => "true".to_owned(),
+        _ => match field.width {
+            Width::Text(_) => "kubos".to_owned(),
+            Width::F32 | Width::F64 => "-12.5".to_owned(),
+            // Convert back from what gets sent, so the value fits no matter the scaling
+            Width::I8 | Width::I16 | Width::I32 => {
+                ((-20.0 - field.offset) / field.scale).to_string()
+            }
+            _ => ((20.0 - field.offset) / field.scale).to_string(),
+        },
+    }
+}
+
+// A snapshot holding a sample value for everything the packet needs from the telemetry database
+fn sample_snapshot(packet: &Packet) -> Snapshot {
+    let mut snapshot = Snapshot::default();
+    for field in packet.fields {
+        if let Some((subsystem, parameter)) = field.source.parameter() {
+            let latest = Latest {
+                value: sample(field),
+                timestamp: 1000.0,
+            };
+            snapshot.insert(subsystem, parameter, latest);
+        }
+    }
+    snapshot
+}
+
+#[test]
+fn every_packet_round_trips() {
+    for packet in PACKETS {
+        let snapshot = sample_snapshot(packet);
+        let frame = frame(packet, |field| match field.source {
+            Source::Local => field.parse(&sample(field)),
+            _ => snapshot.value(field),
+        });
+        assert!(
+            frame.len() - 1 <= MAX_PAYLOAD,
+            "{} is too long",
+            packet.name
+        );
+
+        let beacon = decode(&frame).unwrap();
+        assert_eq!(beacon.header, frame[0]);
+        assert_eq!(beacon.msg_type, packet.msg_type);
+        assert_eq!(beacon.subtype, packet.subtype);
+        assert_eq!(beacon.packet.name, packet.name);
+        assert_eq!(beacon.fields.len(), packet.fields.len());
+
+        for (field, decoded) in packet.fields.iter().zip(beacon.fields.iter()) {
+            assert_eq!(field.name, decoded.name);
+            let sent = sample(field);
+            match field.source {
+                Source::Named(..) => assert_eq!(decoded.label, Some(sent.as_str())),
+                Source::Hex(..) => assert_eq!(decoded.raw, Raw::Unsigned(0x1F)),
+                Source::Flag(..) => assert_eq!(decoded.raw, Raw::Unsigned(1)),
+                _ => match decoded.raw {
+                    Raw::Text(ref text) => assert_eq!(text, &sent),
+                    _ => {
+                        let expected: f64 = sent.parse().unwrap();
+                        let raw = match decoded.raw {
+                            Raw::Unsigned(raw) => raw as f64,
+                            Raw::Signed(raw) => raw as f64,
+                            Raw::Float(raw) => raw,
+                            Raw::Text(_) => unreachable!(),
+                        };
+                        // Undo the scaling to get back to what was read from the database
+                        let value = (raw - field.offset) / field.scale;
+                        assert!(
+                            (value - expected).abs() <= 0.5 / field.scale,
+                            "{}.{}: sent {}, got {}",
+                            packet.name,
+                            field.name,
+                            expected,
+                            value
+                        );
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[test]
+fn missing_values_use_defaults() {
+    for packet in PACKETS {
+        let beacon = decode(&frame(packet, |_| None)).unwrap();
+        for (field, decoded) in packet.fields.iter().zip(beacon.fields.iter()) {
+            if let Width::Text(_) = field.width {
+                assert_eq!(decoded.raw, Raw::Text(String::new()));
+            } else {
+                assert!(decoded.default, "{}.{}", packet.name, field.name);
+            }
+        }
+    }
+}
+
+#[test]
+fn values_are_clamped_and_masked() {
+    let frame = frame(&beacon_schema::TEMPERATURE, |field| match field.name {
+        "eps_mb_temp" => field.parse("-200.0"),
+        "eps_db_temp" => field.parse("200.0"),
+        // 0.1*K
+        "bm2_temp" => field.parse("2981"),
+        "bim_temp0" => field.parse("250.0"),
+        "bm2_temp_range" => field.parse("200"),
+        _ => None,
+    });
+    let beacon = decode(&frame).unwrap();
+
+    assert_eq!(beacon.field("eps_mb_temp").unwrap().raw, Raw::Signed(-128));
+    assert_eq!(beacon.field("eps_db_temp").unwrap().raw, Raw::Signed(127));
+    // 24.95C, truncated like the original packers did
+    assert_eq!(beacon.field("bm2_temp").unwrap().raw, Raw::Signed(24));
+    assert_eq!(beacon.field("bim_temp0").unwrap().raw, Raw::Signed(-23));
+    assert_eq!(
+        beacon.field("bm2_temp_range").unwrap().raw,
+        Raw::Unsigned(8)
+    );
+}
+
+#[test]
+fn text_is_padded_and_truncated() {
+    let frame = frame(&beacon_schema::APP_ERRORS, |field| match field.name {
+        "app_errors_count" => Some(Value::Number(3.0)),
+        "app_last_source" => Some(Value::Text("telem-app".to_owned())),
+        "app_last_msg" => Some(Value::Text("short".to_owned())),
+        _ => None,
+    });
+    assert_eq!(frame.len(), 35);
+
+    let beacon = decode(&frame).unwrap();
+    assert_eq!(
+        beacon.field("app_errors_count").unwrap().raw,
+        Raw::Unsigned(3)
+    );
+    assert_eq!(
+        beacon.field("app_last_source").unwrap().raw,
+        Raw::Text("telem-ap".to_owned())
+    );
+    assert_eq!(
+        beacon.field("app_last_msg").unwrap().raw,
+        Raw::Text("short".to_owned())
+    );
+}
+
+#[test]
+fn scaled_values_are_sent_in_packet_units() {
+    let frame = frame(&beacon_schema::GENERAL_POWER, |field| match field.name {
+        // V, sent as mV
+        "voltage_12v" => field.parse("12.05"),
+        _ => None,
+    });
+    let beacon = decode(&frame).unwrap();
+    let field = beacon.field("voltage_12v").unwrap();
+
+    assert_eq!(field.raw, Raw::Signed(12050));
+    assert_eq!(field.to_json(), serde_json::json!(12050));
+}
+
+#[test]
+fn decodes_flight_frames() {
+    // Temperature and OBC beacons received through NSL
+    let beacon = decode_hex("3811105757DEDEDFDF0B19E5E5E5E5E5E516272704").unwrap();
+    assert_eq!(beacon.packet.name, "temperature");
+    assert_eq!(beacon.field("eps_mb_temp").unwrap().raw, Raw::Signed(0x11));
+    assert_eq!(
+        beacon.field("eps_bcr8a_temp").unwrap().raw,
+        Raw::Signed(-34)
+    );
+    assert_eq!(
+        beacon.field("bm2_temp_range").unwrap().raw,
+        Raw::Unsigned(4)
+    );
+
+    let beacon = decode_hex("18590300").unwrap();
+    assert_eq!(beacon.packet.name, "obc");
+    assert_eq!(
+        beacon.field("ram_available").unwrap().raw,
+        Raw::Unsigned(0x59)
+    );
+    assert_eq!(beacon.field("disk_in_use").unwrap().raw, Raw::Unsigned(3));
+    assert_eq!(beacon.field("deployed").unwrap().raw, Raw::Unsigned(0));
+}
+
+#[test]
+fn rejects_bad_frames() {
+    assert!(decode(&[]).is_err());
+    // Unused subtype
+    assert!(decode_hex("1F00").is_err());
+    // Too short
+    assert!(decode_hex("185903").is_err());
+    assert!(decode_hex("18590").is_err());
+    assert!(decode_hex("18zz0300").is_err());
+}
+
+#[test]
+fn parses_nsl_records() {
+    let input = r#"[
+        {"PayloadID":"748348","Payload":"18590300","DT_NSLReceived":"2017-12-19 13:20:04"},
+        {"PayloadID":"748349","Payload":"1859","DT_NSLReceived":"2017-12-19 13:20:05"}
+    ]"#;
+    let records = parse_records(input).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].id.as_deref(), Some("748348"));
+    assert_eq!(records[0].received.as_deref(), Some("2017-12-19 13:20:04"));
+
+    let json = to_json(&records[0], &records[0].decode());
+    assert_eq!(json["packet"], "obc");
+    assert_eq!(json["type"], "OBC");
+    assert_eq!(json["fields"]["ram_available"], 0x59);
+
+    let json = to_json(&records[1], &records[1].decode());
+    assert!(json["error"].is_string());
+    assert!(json.get("fields").is_none());
+
+    // One record per line works too
+    let input = r#"{"PayloadID":"1","Payload":"18590300"}
+{"PayloadID":"2","Payload":"18590301"}"#;
+    assert_eq!(parse_records(input).unwrap().len(), 2);
+}
+
+#[test]
+fn parses_hex_payloads() {
+    let records = parse_records("18590300\n 0x18590301 ").unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(
+        records[1].decode().unwrap().field("deployed").unwrap().raw,
+        Raw::Unsigned(1)
+    );
+}
+
+#[test]
+fn writes_csv() {
+    let record = Record {
+        id: Some("9".to_owned()),
+        payload: "18590300".to_owned(),
+        ..Default::default()
+    };
+    let lines = to_csv(&record, &record.decode());
+    assert_eq!(
+        lines,
+        vec![
+            "9,,obc,ram_available,89,89",
+            "9,,obc,disk_in_use,3,3",
+            "9,,obc,deployed,0,0",
+        ]
+    );
+
+    let frame = frame(&beacon_schema::APP_ERRORS, |field| match field.name {
+        "app_last_msg" => Some(Value::Text("a, \"b\"".to_owned())),
+        _ => None,
+    });
+    let hex: String = frame.iter().map(|byte| format!("{:02X}", byte)).collect();
+    let record = Record {
+        payload: hex,
+        ..Default::default()
+    };
+    let lines = to_csv(&record, &record.decode());
+    assert_eq!(
+        lines[3],
+        ",,app_errors,app_last_msg,\"a, \"\"b\"\"\",\"a, \"\"b\"\"\""
+    );
+}
+
+#[test]
+fn errors_come_from_log_files() {
+    let log = ErrorLog::parse(
+        b"12 /var/log/app-warn.log\n",
+        b"2019-04-01T16:00:00.123456+00:00 kubos beacon-app[512]:<warn> Failed to send beacon\n",
+    );
+    let beacon = decode(&frame(&beacon_schema::APP_ERRORS, |field| log.value(field))).unwrap();
+
+    assert_eq!(
+        beacon.field("app_errors_count").unwrap().raw,
+        Raw::Unsigned(12)
+    );
+    assert_eq!(
+        beacon.field("app_last_timestamp").unwrap().raw,
+        Raw::Unsigned(u64::from(1_554_134_400_i64 as u16))
+    );
+    assert_eq!(
+        beacon.field("app_last_source").unwrap().raw,
+        Raw::Text("beacon-a".to_owned())
+    );
+    assert_eq!(
+        beacon.field("app_last_msg").unwrap().raw,
+        Raw::Text("Failed to send beacon".to_owned())
+    );
+
+    // An empty log has a count, but nothing else
+    let log = ErrorLog::parse(b"0 /var/log/kubos-warn.log\n", b"");
+    let beacon = decode(&frame(&beacon_schema::SERVICE_ERRORS, |field| {
+        log.value(field)
+    }))
+    .unwrap();
+    assert_eq!(
+        beacon.field("service_errors_count").unwrap().raw,
+        Raw::Unsigned(0)
+    );
+    assert_eq!(
+        beacon.field("service_last_timestamp").unwrap().raw,
+        Raw::Unsigned(0)
+    );
+    assert_eq!(
+        beacon.field("service_last_msg").unwrap().raw,
+        Raw::Text(String::new())
+    );
+}
+
+#[test]
+fn obc_status_comes_from_meminfo_and_df() {
+    let df = b"Filesystem     1K-blocks  Used Available Use% Mounted on
+/dev/mmcblk0p4   3057704  6760   2875612   1% /home
+";
+    let status = ObcStatus {
+        ram_available: ram_percent("257670"),
+        disk_in_use: disk_percent(df),
+        deployed: true,
+    };
+    let beacon = decode(&frame(&beacon_schema::OBC, |field| status.value(field))).unwrap();
+
+    assert_eq!(
+        beacon.field("ram_available").unwrap().raw,
+        Raw::Unsigned(50)
+    );
+    assert_eq!(beacon.field("disk_in_use").unwrap().raw, Raw::Unsigned(1));
+    assert_eq!(beacon.field("deployed").unwrap().raw, Raw::Unsigned(1));
+
+    // Anything unreadable counts as full
+    assert_eq!(disk_percent(b""), 100);
+}
+
+#[test]
+fn gps_status_flags_are_combined() {
+    let mut snapshot = Snapshot::default();
+    let mut flag = |num: usize, value: &str, timestamp: f64| {
+        let latest = Latest {
+            value: value.to_owned(),
+            timestamp,
+        };
+        snapshot.insert("OEM", &format!("systemStatus_status_{}", num), latest);
+    };
+    // telem-app stores the flags from one query with the same timestamp
+    flag(0, "ERROR_PRESENT", 100.0);
+    flag(1, "ANTENNA_NOT_POWERED", 100.0);
+    flag(2, "VOLTAGE_SUPPLY_WARNING", 101.5);
+    // Left over from an older query
+    flag(3, "TEMPERATURE_WARNING", 40.0);
+
+    let beacon = decode(&frame(&beacon_schema::GPS_MISC, |field| {
+        lookup::gps_misc(&snapshot, Some(1), field)
+    }))
+    .unwrap();
+
+    assert_eq!(
+        beacon.field("system_status").unwrap().raw,
+        Raw::Unsigned(0x0000_000D)
+    );
+    assert_eq!(beacon.field("power").unwrap().raw, Raw::Unsigned(1));
+
+    // No power reading is sent as the default
+    let beacon = decode(&frame(&beacon_schema::GPS_MISC, |field| {
+        lookup::gps_misc(&snapshot, None, field)
+    }))
+    .unwrap();
+    assert!(beacon.field("power").unwrap().default);
+}
@@ -0,0 +1,56 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// The simplex gateway parses beacons with tables generated by `beacon-decoder --tables`. Make
+// sure the copy it's using hasn't drifted from the packet definitions
+
+use beacon_decoder::tables;
+use beacon_schema::PACKETS;
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn gateway_tables_are_up_to_date() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../simplex-gateway/kubos_gateway/beacon_tables.json");
+    let saved: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+
+    assert_eq!(
+        saved,
+        tables(),
+        "Regenerate {} with `beacon-decoder --tables`",
+        path.display()
+    );
+}
+
+#[test]
+fn tables_cover_every_packet() {
+    let tables = tables();
+    let packets = tables["packets"].as_array().unwrap();
+    assert_eq!(packets.len(), PACKETS.len());
+
+    for (table, packet) in packets.iter().zip(PACKETS.iter()) {
+        assert_eq!(table["header"], packet.header());
+        assert_eq!(
+            table["names"].as_array().unwrap().len(),
+            packet.fields.len()
+        );
+    }
+
+    let obc = packets.iter().find(|table| table["name"] == "obc").unwrap();
+    assert_eq!(obc["parsing"], "<BBB");
+}
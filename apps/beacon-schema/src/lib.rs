@@ -0,0 +1,403 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Beacon packet definitions, shared by beacon-app and the ground
+//
+// Each packet is described once, in `packets.rs`: its message type and subtype, and for each
+// field, where the value comes from, how it's scaled, how many bytes it takes up, and what gets
+// sent when the value isn't available. beacon-app builds frames from these definitions and the
+// ground decodes frames with them, so the two can't drift apart.
+//
+// Every frame starts with a header byte, followed by the packet's fields in order. All
+// multi-byte fields are Little Endian.
+
+mod packets;
+
+pub use crate::packets::*;
+
+use failure::{bail, Error};
+
+// The most data bytes the simplex can send in one message (not including the header)
+pub const MAX_PAYLOAD: usize = 34;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum MessageType {
+    ADCS = 0,
+    Errors = 1,
+    GPS = 2,
+    OBC = 3,
+    Power = 4,
+    Radio = 5,
+    SupMCU = 6,
+    Temperature = 7,
+}
+
+impl MessageType {
+    // The message type from a header byte (see `header`)
+    pub fn from_header(header: u8) -> Option<MessageType> {
+        match header >> 3 {
+            0 => Some(MessageType::ADCS),
+            1 => Some(MessageType::Errors),
+            2 => Some(MessageType::GPS),
+            3 => Some(MessageType::OBC),
+            4 => Some(MessageType::Power),
+            5 => Some(MessageType::Radio),
+            6 => Some(MessageType::SupMCU),
+            7 => Some(MessageType::Temperature),
+            _ => None,
+        }
+    }
+}
+
+// Combine message type and subtype into single header byte
+// 7 6 5 4 3 | 2 1 0
+//  Msg type | Sub type
+pub fn header(msg_type: MessageType, subtype: u8) -> u8 {
+    ((msg_type as u8) << 3) | (subtype & 0x07)
+}
+
+// The subtype from a header byte
+pub fn subtype(header: u8) -> u8 {
+    header & 0x07
+}
+
+// Size and encoding of a field
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Width {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    F64,
+    // Fixed-length text. Shorter strings are padded with zeros, longer ones are truncated
+    Text(usize),
+}
+
+impl Width {
+    pub fn size(self) -> usize {
+        match self {
+            Width::U8 | Width::I8 => 1,
+            Width::U16 | Width::I16 => 2,
+            Width::U32 | Width::I32 | Width::F32 => 4,
+            Width::F64 => 8,
+            Width::Text(len) => len,
+        }
+    }
+}
+
+// Where a field's value comes from. Everything other than `Local` is the latest value of a
+// telemetry database entry, given as (subsystem, parameter)
+#[derive(Clone, Copy, Debug)]
+pub enum Source {
+    // A number
+    Number(&'static str, &'static str),
+    // A number stored as a hex string (ex. "00C0")
+    Hex(&'static str, &'static str),
+    // A boolean ("true"/"false"), sent as 1 or 0
+    Flag(&'static str, &'static str),
+    // One of a fixed set of names, sent as the matching code
+    Named(&'static str, &'static str, &'static [(&'static str, u32)]),
+    // Gathered by beacon-app itself (ex. disk usage, log file contents)
+    Local,
+}
+
+impl Source {
+    // The telemetry database entry this value comes from, if any
+    pub fn parameter(&self) -> Option<(&'static str, &'static str)> {
+        match *self {
+            Source::Number(subsystem, parameter)
+            | Source::Hex(subsystem, parameter)
+            | Source::Flag(subsystem, parameter)
+            | Source::Named(subsystem, parameter, _) => Some((subsystem, parameter)),
+            Source::Local => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+}
+
+impl Value {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(value) => Some(*value),
+            Value::Text(_) => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Field {
+    pub name: &'static str,
+    pub source: Source,
+    pub width: Width,
+    // Bits of the value to keep (ex. 0x3F to drop reserved flags)
+    pub mask: Option<u64>,
+    // The value sent is `value * scale + offset`, truncated towards zero for integer fields (the
+    // same as the original packers, so existing ground decoding still matches).
+    // Out of range values are clamped to the field's limits
+    pub scale: f64,
+    pub offset: f64,
+    // Sent as-is (no scaling) when the value isn't available
+    pub default: f64,
+}
+
+impl Field {
+    pub const fn new(name: &'static str, source: Source, width: Width) -> Field {
+        Field {
+            name,
+            source,
+            width,
+            mask: None,
+            scale: 1.0,
+            offset: 0.0,
+            default: 0.0,
+        }
+    }
+
+    pub const fn scaled(self, scale: f64, offset: f64) -> Field {
+        Field {
+            scale,
+            offset,
+            ..self
+        }
+    }
+
+    pub const fn masked(self, mask: u64) -> Field {
+        Field {
+            mask: Some(mask),
+            ..self
+        }
+    }
+
+    pub const fn with_default(self, default: f64) -> Field {
+        Field { default, ..self }
+    }
+
+    // Convert a value from the telemetry database (or any other text), based on how it's stored.
+    // Returns `None` if it isn't in the expected format
+    pub fn parse(&self, raw: &str) -> Option<Value> {
+        let raw = raw.trim();
+        let value = match self.source {
+            Source::Number(..) => raw.parse().ok()?,
+            Source::Hex(..) => u64::from_str_radix(raw, 16).ok()? as f64,
+            Source::Flag(..) => f64::from(raw.parse::<bool>().ok()? as u8),
+            Source::Named(_, _, names) => f64::from(
+                names
+                    .iter()
+                    .find(|(name, _)| *name == raw)
+                    .map(|(_, code)| *code)?,
+            ),
+            Source::Local => match self.width {
+                Width::Text(_) => return Some(Value::Text(raw.to_owned())),
+                _ => raw.parse().ok()?,
+            },
+        };
+
+        Some(Value::Number(value))
+    }
+
+    // The name matching a `Named` field's code
+    pub fn label(&self, code: f64) -> Option<&'static str> {
+        match self.source {
+            Source::Named(_, _, names) => names
+                .iter()
+                .find(|(_, value)| f64::from(*value) == code)
+                .map(|(name, _)| *name),
+            _ => None,
+        }
+    }
+
+    fn encode(&self, value: Option<Value>, msg: &mut Vec<u8>) {
+        if let Width::Text(len) = self.width {
+            let text = match value {
+                Some(Value::Text(text)) => text,
+                _ => String::new(),
+            };
+            let mut bytes = text.into_bytes();
+            bytes.resize(len, 0);
+            msg.extend_from_slice(&bytes);
+            return;
+        }
+
+        let raw = match value.as_ref().and_then(Value::as_f64) {
+            Some(mut value) if value.is_finite() => {
+                if let Some(mask) = self.mask {
+                    value = ((value as u64) & mask) as f64;
+                }
+                value * self.scale + self.offset
+            }
+            _ => self.default,
+        };
+
+        // Float to integer casts truncate and saturate, which clamps the value to the field's limits
+        match self.width {
+            Width::U8 => msg.push(raw as u8),
+            Width::I8 => msg.push(raw as i8 as u8),
+            Width::U16 => msg.extend_from_slice(&(raw as u16).to_le_bytes()),
+            Width::I16 => msg.extend_from_slice(&(raw as i16).to_le_bytes()),
+            Width::U32 => msg.extend_from_slice(&(raw as u32).to_le_bytes()),
+            Width::I32 => msg.extend_from_slice(&(raw as i32).to_le_bytes()),
+            Width::F32 => msg.extend_from_slice(&(raw as f32).to_le_bytes()),
+            Width::F64 => msg.extend_from_slice(&raw.to_le_bytes()),
+            Width::Text(_) => unreachable!(),
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Value {
+        if let Width::Text(_) = self.width {
+            let end = bytes
+                .iter()
+                .position(|&byte| byte == 0)
+                .unwrap_or(bytes.len());
+            return Value::Text(String::from_utf8_lossy(&bytes[..end]).into_owned());
+        }
+
+        let mut buf = [0; 8];
+        buf[..bytes.len()].copy_from_slice(bytes);
+
+        let raw = match self.width {
+            Width::U8 => f64::from(bytes[0]),
+            Width::I8 => f64::from(bytes[0] as i8),
+            Width::U16 => f64::from(u16::from_le_bytes([buf[0], buf[1]])),
+            Width::I16 => f64::from(i16::from_le_bytes([buf[0], buf[1]])),
+            Width::U32 => f64::from(u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])),
+            Width::I32 => f64::from(i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])),
+            Width::F32 => f64::from(f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])),
+            Width::F64 => f64::from_le_bytes(buf),
+            Width::Text(_) => unreachable!(),
+        };
+
+        Value::Number(raw)
+    }
+}
+
+#[derive(Debug)]
+pub struct Packet {
+    pub name: &'static str,
+    pub msg_type: MessageType,
+    pub subtype: u8,
+    pub fields: &'static [Field],
+}
+
+impl Packet {
+    pub fn header(&self) -> u8 {
+        header(self.msg_type, self.subtype)
+    }
+
+    // Number of data bytes in the packet (not including the header)
+    pub fn size(&self) -> usize {
+        self.fields.iter().map(|field| field.width.size()).sum()
+    }
+
+    // Build the packet's data bytes. `lookup` supplies the current value of each field, or
+    // `None` if it isn't available
+    pub fn encode<F>(&self, mut lookup: F) -> Vec<u8>
+    where
+        F: FnMut(&Field) -> Option<Value>,
+    {
+        let mut msg = Vec::with_capacity(self.size());
+        for field in self.fields {
+            field.encode(lookup(field), &mut msg);
+        }
+        msg
+    }
+
+    // Split the packet's data bytes back into its fields
+    pub fn decode(&'static self, payload: &[u8]) -> Result<Vec<Reading>, Error> {
+        if payload.len() != self.size() {
+            bail!(
+                "{} packet should be {} bytes, got {}",
+                self.name,
+                self.size(),
+                payload.len()
+            );
+        }
+
+        let mut offset = 0;
+        let readings = self
+            .fields
+            .iter()
+            .map(|field| {
+                let size = field.width.size();
+                let raw = field.decode(&payload[offset..offset + size]);
+                offset += size;
+                Reading { field, raw }
+            })
+            .collect();
+
+        Ok(readings)
+    }
+}
+
+// A single decoded field
+#[derive(Clone, Debug)]
+pub struct Reading {
+    pub field: &'static Field,
+    // The value exactly as it was sent
+    pub raw: Value,
+}
+
+impl Reading {
+    // Whether the field was filled in because its value wasn't available.
+    // Note that some defaults (ex. 0) are also perfectly good values
+    pub fn is_default(&self) -> bool {
+        self.raw.as_f64() == Some(self.field.default)
+    }
+
+    // The value with the field's scaling undone
+    pub fn value(&self) -> Value {
+        match self.raw {
+            Value::Number(raw) => Value::Number((raw - self.field.offset) / self.field.scale),
+            Value::Text(ref text) => Value::Text(text.clone()),
+        }
+    }
+
+    // The name matching the sent code, for fields sent as one of a fixed set of names
+    pub fn label(&self) -> Option<&'static str> {
+        self.raw.as_f64().and_then(|raw| self.field.label(raw))
+    }
+}
+
+// Look up the packet with the given header byte
+pub fn find(header: u8) -> Option<&'static Packet> {
+    PACKETS
+        .iter()
+        .find(|packet| packet.header() == header)
+        .copied()
+}
+
+// Decode a full frame (header byte followed by the packet's data)
+pub fn decode(frame: &[u8]) -> Result<(&'static Packet, Vec<Reading>), Error> {
+    let (header, payload) = match frame.split_first() {
+        Some(split) => split,
+        None => bail!("Empty frame"),
+    };
+
+    let packet = match find(*header) {
+        Some(packet) => packet,
+        None => bail!("Unknown header {:#04x}", header),
+    };
+
+    Ok((packet, packet.decode(payload)?))
+}
@@ -0,0 +1,524 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// The layout of every beacon packet
+//
+// Field names match the ones used by the ground's decoder tables. Don't reorder, resize or
+// remove fields from a packet which is already flying; add a new subtype instead.
+
+use crate::Source::{Flag, Hex, Local, Named, Number};
+use crate::Width::*;
+use crate::{Field, MessageType, Packet, Width};
+
+pub static PACKETS: &[&Packet] = &[
+    &ADCS1,
+    &ADCS2,
+    &APP_ERRORS,
+    &SERVICE_ERRORS,
+    &GPS_POSITION,
+    &GPS_VELOCITY,
+    &GPS_MISC,
+    &OBC,
+    &GENERAL_POWER,
+    &BATTERY_MB_POWER,
+    &DB_POWER,
+    &RADIO,
+    &SUPMCU,
+    &TEMPERATURE,
+];
+
+// Shorthand for the most common kind of field: a number straight from the telemetry database
+const fn number(
+    name: &'static str,
+    subsystem: &'static str,
+    parameter: &'static str,
+    width: Width,
+) -> Field {
+    Field::new(name, Number(subsystem, parameter), width)
+}
+
+const fn local(name: &'static str, width: Width) -> Field {
+    Field::new(name, Local, width)
+}
+
+// MAI-400 attitude control modes
+pub const ACS_MODES: &[(&str, u32)] = &[
+    ("TEST_MODE", 0),
+    ("RATE_NULLING", 1),
+    ("RESERVED1", 2),
+    ("NADIR_POINTING", 3),
+    ("LAT_LONG_POINTING", 4),
+    ("QBX_MODE", 5),
+    ("RESERVED2", 6),
+    ("NORMAL_SUN", 7),
+    ("LAT_LONG_SUN", 8),
+    ("QINTERTIAL", 9),
+    ("RESERVED3", 10),
+    ("QTABLE", 11),
+    ("SUN_RAM", 12),
+];
+
+// NovAtel solution statuses
+pub const SOLUTION_STATUSES: &[(&str, u32)] = &[
+    ("SOL_COMPUTED", 0),
+    ("INSUFFICIENT_OBSERVATIONS", 1),
+    ("NO_CONVERGENCE", 2),
+    ("SINGULARITY", 3),
+    ("COVARIANCE_TRACE_EXCEEDED", 4),
+    ("TEST_DISTANCE_EXCEEDED", 5),
+    ("COLD_START", 6),
+    ("HEIGHT_VELOCITY_EXCEEDED", 7),
+    ("VARIANCE_EXCEEDED", 8),
+    ("RESIDUALS_TOO_LARGE", 9),
+    ("INTEGRITY_WARNING", 13),
+    ("PENDING", 18),
+    ("INVALID_FIX", 19),
+    ("UNAUTHORIZED", 20),
+];
+
+// NovAtel position and velocity types
+pub const POSVEL_TYPES: &[(&str, u32)] = &[
+    ("NONE", 0),
+    ("FIXED_POS", 1),
+    ("FIXED_HEIGHT", 2),
+    ("DOPPLER_VELOCITY", 8),
+    ("SINGLE", 16),
+    ("PSRDIFF", 17),
+    ("WAAS", 18),
+    ("PROPAGATED", 19),
+    ("OMNISTAR", 20),
+    ("L1FLOAT", 32),
+    ("IONO_FREE_FLOAT", 33),
+    ("NARROW_FLOAT", 34),
+    ("L1INTEGER", 48),
+    ("NARROW_INTEGER", 50),
+    ("OMNISTAR_HP", 64),
+    ("OMNISTAR_XP", 65),
+    ("PPPCONVERGING", 68),
+    ("PPP", 69),
+    ("OPERATIONAL", 70),
+    ("WARNING", 71),
+    ("OUT_OF_BOUNDS", 72),
+    ("PPPBASIC_CONVERGING", 77),
+    ("PPPBASIC", 78),
+];
+
+// NovAtel time statuses. The higher the code, the better the receiver knows the time
+pub const TIME_STATUSES: &[(&str, u32)] = &[
+    ("UNKNOWN", 20),
+    ("APPROXIMATE", 60),
+    ("COARSE_ADJUSTING", 80),
+    ("COARSE", 100),
+    ("COARSE_STEERING", 120),
+    ("FREE_WHEELING", 130),
+    ("FINE_ADJUSTING", 140),
+    ("FINE", 160),
+    ("FINE_BACKUP_STEERING", 170),
+    ("FINE_STEERING", 180),
+    ("SAT_TIME", 200),
+];
+
+// NovAtel receiver status flags. Each one that's set is OR'd into the GPS `system_status` field
+pub const SYSTEM_STATUS_FLAGS: &[(&str, u32)] = &[
+    ("ERROR_PRESENT", 0x0000_0001),
+    ("TEMPERATURE_WARNING", 0x0000_0002),
+    ("VOLTAGE_SUPPLY_WARNING", 0x0000_0004),
+    ("ANTENNA_NOT_POWERED", 0x0000_0008),
+    ("LNA_FAILURE", 0x0000_0010),
+    ("ANTENNA_OPEN", 0x0000_0020),
+    ("ANTENNA_SHORTENED", 0x0000_0040),
+    ("CPU_OVERLOAD", 0x0000_0080),
+    ("COM1_BUFFER_OVERRUN", 0x0000_0100),
+    ("COM2_BUFFER_OVERRUN", 0x0000_0200),
+    ("COM3_BUFFER_OVERRUN", 0x0000_0400),
+    ("LINK_OVERRUN", 0x0000_0800),
+    ("AUX_TRANSMIT_OVERRUN", 0x0000_2000),
+    ("AGC_OUT_OF_RANGE", 0x0000_4000),
+    ("INS_RESET", 0x0001_0000),
+    ("GPS_ALMANAC_INVALID", 0x0004_0000),
+    ("POSITION_SOLUTION_INVALID", 0x0008_0000),
+    ("POSITION_FIXED", 0x0010_0000),
+    ("CLOCK_STEERING_DISABLED", 0x0020_0000),
+    ("CLOCK_MODEL_INVALID", 0x0040_0000),
+    ("EXTERNAL_OSCILLATOR_LOCKED", 0x0080_0000),
+    ("SOFTWARE_RESOURCE_WARNING", 0x0100_0000),
+    ("AUX3_STATUS_EVENT", 0x2000_0000),
+    ("AUX2_STATUS_EVENT", 0x4000_0000),
+    ("AUX1_STATUS_EVENT", 0x8000_0000),
+];
+
+// ADCS, packet 1 (general status)
+//
+// - `acs_mode`: See `ACS_MODES`
+// - `attdet_mode`: 0 = CSS/magnetometer, 1 = Set Qbi, 2 = EHS/magnetometer
+// - `eclipse`: 0 = not eclipsed, 1 = eclipsed
+// - `angle_to_go`: Net angle required before target attitude is achieved
+pub static ADCS1: Packet = Packet {
+    name: "adcs1",
+    msg_type: MessageType::ADCS,
+    subtype: 1,
+    fields: &[
+        number("gps_time", "MAI400", "gpsTime", U32),
+        number("good_cmd_count", "MAI400", "cmdValidCntr", U16),
+        number("bad_cmd_count", "MAI400", "cmdInvalidCntr", U16),
+        number("bad_checksum_count", "MAI400", "cmdInvalidChksumCntr", U16),
+        number("last_command", "MAI400", "lastCommand", U8),
+        Field::new("acs_mode", Named("MAI400", "acsMode", ACS_MODES), U8).with_default(255.0),
+        number("attdet_mode", "MAI400", "attDetMode", U8).with_default(255.0),
+        number("eclipse", "MAI400", "eclipseFlag", U8).with_default(255.0),
+        number("angle_to_go", "MAI400", "angleToGo", F32),
+    ],
+};
+
+// ADCS, packet 2 (body rates, wheel speeds and current estimated orbit-to-body quaternion)
+pub static ADCS2: Packet = Packet {
+    name: "adcs2",
+    msg_type: MessageType::ADCS,
+    subtype: 2,
+    fields: &[
+        number("body_rate_x", "MAI400", "omegaB_0", F32),
+        number("body_rate_y", "MAI400", "omegaB_1", F32),
+        number("body_rate_z", "MAI400", "omegaB_2", F32),
+        number("wheel_speed_x", "MAI400", "rwsSpeedTach_0", I16),
+        number("wheel_speed_y", "MAI400", "rwsSpeedTach_1", I16),
+        number("wheel_speed_z", "MAI400", "rwsSpeedTach_2", I16),
+        number("wheel_bias_x", "MAI400", "wheelSpeedBias_0", I16),
+        number("wheel_bias_y", "MAI400", "wheelSpeedBias_1", I16),
+        number("wheel_bias_z", "MAI400", "wheelSpeedBias_2", I16),
+        number("qbo_0", "MAI400", "qboHat_0", I16),
+        number("qbo_1", "MAI400", "qboHat_1", I16),
+        number("qbo_2", "MAI400", "qboHat_2", I16),
+        number("qbo_3", "MAI400", "qboHat_3", I16),
+    ],
+};
+
+// Errors, packet 1 (application errors)
+//
+// - `*_errors_count`: Number of lines in the log file
+// - `*_last_timestamp`: UTC timestamp of the last error, in seconds, reduced to a u16
+// - `*_last_source`: Source of the last error
+// - `*_last_msg`: The last error message
+pub static APP_ERRORS: Packet = Packet {
+    name: "app_errors",
+    msg_type: MessageType::Errors,
+    subtype: 1,
+    fields: &[
+        local("app_errors_count", U16).with_default(65535.0),
+        local("app_last_timestamp", U16),
+        local("app_last_source", Text(8)),
+        local("app_last_msg", Text(22)),
+    ],
+};
+
+// Errors, packet 2 (service errors). Same as packet 1
+pub static SERVICE_ERRORS: Packet = Packet {
+    name: "service_errors",
+    msg_type: MessageType::Errors,
+    subtype: 2,
+    fields: &[
+        local("service_errors_count", U16).with_default(65535.0),
+        local("service_last_timestamp", U16),
+        local("service_last_source", Text(8)),
+        local("service_last_msg", Text(22)),
+    ],
+};
+
+// GPS, packet 1 (position)
+pub static GPS_POSITION: Packet = Packet {
+    name: "gps_position",
+    msg_type: MessageType::GPS,
+    subtype: 1,
+    fields: &[
+        Field::new(
+            "position_status",
+            Named("OEM", "lockStatus_positionStatus", SOLUTION_STATUSES),
+            U8,
+        )
+        .with_default(255.0),
+        Field::new(
+            "position_type",
+            Named("OEM", "lockStatus_positionType", POSVEL_TYPES),
+            U16,
+        )
+        .with_default(65535.0),
+        number("position_x", "OEM", "lockInfo_position_0", F64),
+        number("position_y", "OEM", "lockInfo_position_1", F64),
+        number("position_z", "OEM", "lockInfo_position_2", F64),
+    ],
+};
+
+// GPS, packet 2 (velocity)
+pub static GPS_VELOCITY: Packet = Packet {
+    name: "gps_velocity",
+    msg_type: MessageType::GPS,
+    subtype: 2,
+    fields: &[
+        Field::new(
+            "velocity_status",
+            Named("OEM", "lockStatus_velocityStatus", SOLUTION_STATUSES),
+            U8,
+        )
+        .with_default(255.0),
+        Field::new(
+            "velocity_type",
+            Named("OEM", "lockStatus_velocityType", POSVEL_TYPES),
+            U16,
+        )
+        .with_default(65535.0),
+        number("velocity_x", "OEM", "lockInfo_velocity_0", F64),
+        number("velocity_y", "OEM", "lockInfo_velocity_1", F64),
+        number("velocity_z", "OEM", "lockInfo_velocity_2", F64),
+    ],
+};
+
+// GPS, packet 3 (time and status)
+//
+// - `time_status`: See `TIME_STATUSES`
+// - `time_week`/`time_ms`: Last known GPS time
+// - `system_status`: See `SYSTEM_STATUS_FLAGS`
+// - `gps_status`: Raw AIM2 `status` field, passed through unchanged for the ground to decode.
+//   The flag meanings aren't documented here; no app reads them
+// - `power_status`: Raw AIM2 `gps_power` field, passed through the same way
+// - `power_3v_usb`: Power draw over the 3.3V USB connection (normal value is ~0.9 Watts)
+// - `power`: Power status from the OEM6 service (0 = off, 1 = on)
+// - `lock_time_week`/`lock_time_ms`: GPS time of the last successful lock
+pub static GPS_MISC: Packet = Packet {
+    name: "gps_misc",
+    msg_type: MessageType::GPS,
+    subtype: 3,
+    fields: &[
+        Field::new(
+            "time_status",
+            Named("OEM", "lockStatus_timeStatus", TIME_STATUSES),
+            U8,
+        )
+        .with_default(255.0),
+        number("time_week", "OEM", "lockStatus_time_week", U16),
+        number("time_ms", "OEM", "lockStatus_time_ms", U32),
+        local("system_status", U32),
+        Field::new("gps_status", Hex("aim2", "status"), U16),
+        Field::new("power_status", Hex("aim2", "gps_power"), U8),
+        number("power_3v_usb", "aim2", "oem_power2", F32),
+        local("power", U8).with_default(255.0),
+        number("lock_time_week", "OEM", "lockInfo_time_week", U16),
+        number("lock_time_ms", "OEM", "lockInfo_time_ms", U32),
+    ],
+};
+
+// OBC
+//
+// - `ram_available`: % of RAM available
+// - `disk_in_use`: % of the user data partition (/home) in use
+// - `deployed`: 0 = not deployed, 1 = deployed
+pub static OBC: Packet = Packet {
+    name: "obc",
+    msg_type: MessageType::OBC,
+    subtype: 0,
+    fields: &[
+        local("ram_available", U8),
+        local("disk_in_use", U8).with_default(100.0),
+        local("deployed", U8),
+    ],
+};
+
+// Power, packet 1 (general status)
+//
+// - `pf_status`: Permanent failure status flags (see B.7 of the bq34z653 technical reference)
+// - Capacities are in mAh, voltages in mV and currents in mA
+pub static GENERAL_POWER: Packet = Packet {
+    name: "general_power",
+    msg_type: MessageType::Power,
+    subtype: 1,
+    fields: &[
+        number("voltage", "bm2", "voltage", U16).with_default(65535.0),
+        number("current", "bm2", "current", I16).with_default(32767.0),
+        Field::new("pf_status", Hex("bm2", "perm_fail_status"), U16).with_default(65535.0),
+        number("mb_reset_bo", "EPS", "reset_brownout_mb", U8).with_default(255.0),
+        number("mb_reset_wdt", "EPS", "reset_wd_mb", U8).with_default(255.0),
+        number("mb_reset_sw", "EPS", "reset_sw_mb", U8).with_default(255.0),
+        number("db_reset_bo", "EPS", "reset_brownout_db", U8).with_default(255.0),
+        number("db_reset_wdt", "EPS", "reset_wd_db", U8).with_default(255.0),
+        number("db_reset_sw", "EPS", "reset_sw_db", U8).with_default(255.0),
+        number("remaining_cap", "bm2", "remaining_capacity", U16).with_default(65535.0),
+        number("full_cap", "bm2", "full_capacity", U16).with_default(65535.0),
+        number("charge_voltage", "bm2", "charging_voltage", U16).with_default(65535.0),
+        number("charge_current", "bm2", "charging_current", U16).with_default(65535.0),
+        // The EPS reports voltages in V
+        number("voltage_12v", "EPS", "mb_OutputVoltage12V", I16).scaled(1000.0, 0.0),
+        number("current_12v", "EPS", "mb_OutputCurrent12V", I16),
+        number("voltage_5v", "EPS", "mb_OutputVoltage5v", I16).scaled(1000.0, 0.0),
+        number("current_5v", "EPS", "mb_OutputCurrent5v", I16),
+        number("voltage_3v", "EPS", "mb_OutputVoltage33v", I16).scaled(1000.0, 0.0),
+        number("current_3v", "EPS", "mb_OutputCurrent33v", I16),
+    ],
+};
+
+// Power, packet 2 (battery cells and motherboard solar panels). Voltages in mV, currents in mA
+pub static BATTERY_MB_POWER: Packet = Packet {
+    name: "battery_mb_power",
+    msg_type: MessageType::Power,
+    subtype: 2,
+    fields: &[
+        number("voltage_cell1", "bm2", "cell1_voltage", U16).with_default(65535.0),
+        number("voltage_cell2", "bm2", "cell2_voltage", U16).with_default(65535.0),
+        number("voltage_cell3", "bm2", "cell3_voltage", U16).with_default(65535.0),
+        number("voltage_cell4", "bm2", "cell4_voltage", U16).with_default(65535.0),
+        number("voltage_bcr1", "EPS", "mb_VoltageFeedingBcr1", I16).scaled(1000.0, 0.0),
+        number("current_bcr1a", "EPS", "mb_CurrentBcr1Sa1a", I16),
+        number("current_bcr1b", "EPS", "mb_CurrentBcr1Sa1b", I16),
+        number("voltage_bcr2", "EPS", "mb_VoltageFeedingBcr2", I16).scaled(1000.0, 0.0),
+        number("current_bcr2a", "EPS", "mb_CurrentBcr2Sa2a", I16),
+        number("current_bcr2b", "EPS", "mb_CurrentBcr2Sa2b", I16),
+    ],
+};
+
+// Power, packet 3 (daughterboard solar panels). Voltages in mV, currents in mA
+pub static DB_POWER: Packet = Packet {
+    name: "db_power",
+    msg_type: MessageType::Power,
+    subtype: 3,
+    fields: &[
+        number("voltage_bcr6", "EPS", "db_VoltageFeedingBcr6", I16).scaled(1000.0, 0.0),
+        number("current_bcr6a", "EPS", "db_CurrentBcr6Sa6a", I16),
+        number("current_bcr6b", "EPS", "db_CurrentBcr6Sa6b", I16),
+        number("voltage_bcr7", "EPS", "db_VoltageFeedingBcr7", I16).scaled(1000.0, 0.0),
+        number("current_bcr7a", "EPS", "db_CurrentBcr7Sa7a", I16),
+        number("current_bcr7b", "EPS", "db_CurrentBcr7Sa7b", I16),
+        number("voltage_bcr8", "EPS", "db_VoltageFeedingBcr8", I16).scaled(1000.0, 0.0),
+        number("current_bcr8a", "EPS", "db_CurrentBcr8Sa8a", I16),
+        number("current_bcr8b", "EPS", "db_CurrentBcr8Sa8b", I16),
+        number("voltage_bcr9", "EPS", "db_VoltageFeedingBcr9", I16).scaled(1000.0, 0.0),
+        number("current_bcr9a", "EPS", "db_CurrentBcr9Sa9a", I16),
+        number("current_bcr9b", "EPS", "db_CurrentBcr9Sa9b", I16),
+    ],
+};
+
+// Duplex radio health
+//
+// - `alive`: Modem responding (0 = no, 1 = yes). The radio can't be commanded off, so this is
+//            also the only sign of whether it's powered
+// - `simplex_status`: Simplex status from the RHM
+pub static RADIO: Packet = Packet {
+    name: "radio",
+    msg_type: MessageType::Radio,
+    subtype: 0,
+    fields: &[
+        number("packets_up", "DUPLEX", "packetsUp", U32).with_default(4_294_967_295.0),
+        number("failed_packets_up", "DUPLEX", "failedPacketsUp", U32).with_default(4_294_967_295.0),
+        number("packets_down", "DUPLEX", "packetsDown", U32).with_default(4_294_967_295.0),
+        number("failed_packets_down", "DUPLEX", "failedPacketsDown", U32)
+            .with_default(4_294_967_295.0),
+        number("reset_count", "DUPLEX", "modemHealth_resetCount", U16).with_default(65535.0),
+        number("rssi", "DUPLEX", "modemHealth_currentRssi", U8).with_default(255.0),
+        number(
+            "connection_status",
+            "DUPLEX",
+            "modemHealth_connectionStatus",
+            U8,
+        )
+        .with_default(255.0),
+        number(
+            "last_contact_time",
+            "DUPLEX",
+            "modemHealth_lastContactTime",
+            U32,
+        )
+        .with_default(4_294_967_295.0),
+        number("file_queue_count", "DUPLEX", "fileQueueCount", U16).with_default(65535.0),
+        Field::new("alive", Flag("DUPLEX", "alive"), U8).with_default(255.0),
+        number("simplex_status", "rhm", "globalstar_status", U8).with_default(255.0),
+    ],
+};
+
+// SupMCU module uptimes and reset flags. Modules which aren't present are sent as unavailable
+//
+// Reset Flags (Documentation provided by Pumpkin):
+//     - 0 = Power on Reset (The board was just applied power or cycled power).
+//     - 1 = Brown-out Reset (Unstable VCC into MCU caused reset)
+//     - 2 = MCU woke from idle instr. (not useful)
+//     - 3 = MCU woke from sleep instr. (not useful)
+//     - 4 = WDTO Reset (Software Watchdog on MCU expired) [should not happen]
+//     - 5 = SWDTEN (1 = software WDT is enabled, 0 = not enabled) [should always be 1]
+//     - 6 = SWR Reset (SupMCU was instructed to reset) [SUP:RES NOW was sent as command]
+//     - 7 = External Reset (Bus WDT reset the MCU module)
+//     - 8 = VREGS (not used)
+//     - 9 = Firmware configuration mismatch Reset (not used)
+//     - 10 = not used (reads as 0)
+//     - 11 = VREGSF (not used)
+//     - 12-13 = not used (reads as 0)
+//     - 14 = IOPUWR Reset (Illegal opcode executed) [should not happen]
+//     - 15 = TRAPR (Trap Conflict/interrupt conflict) [should not happen]
+pub static SUPMCU: Packet = Packet {
+    name: "supmcu",
+    msg_type: MessageType::SupMCU,
+    subtype: 0,
+    fields: &[
+        number("aim2_uptime", "aim2", "time", U8),
+        number("aim2_reset", "aim2", "reset_cause", U16).with_default(65535.0),
+        number("bim_uptime", "bim", "time", U8),
+        number("bim_reset", "bim", "reset_cause", U16).with_default(65535.0),
+        number("pim_uptime", "pim", "time", U8),
+        number("pim_reset", "pim", "reset_cause", U16).with_default(65535.0),
+        number("sim_uptime", "sim", "time", U8),
+        number("sim_reset", "sim", "reset_cause", U16).with_default(65535.0),
+        number("rhm_uptime", "rhm", "time", U8),
+        number("rhm_reset", "rhm", "reset_cause", U16).with_default(65535.0),
+        number("bm2_uptime", "bm2", "time", U8),
+        number("bm2_reset", "bm2", "reset_cause", U16).with_default(65535.0),
+    ],
+};
+
+// All available temperature readings, in whole degrees C
+//
+// Note: BCR 1, 2, 6, 7, 8, and 9 are connected, but only 2, 8, and 9 have temperature sensors
+//
+// `bm2_temp_range` is a bit field (see section B.30 of the bq34z653 Technical Reference):
+// - 0x01: Temp < JT1 (below minimum operating temperature)
+// - 0x02: JT1  < Temp < JT2  (low, but okay temperature)
+// - 0x04: JT2  < Temp < JT2a (nominal temperature range 1)
+// - 0x08: JT2a < Temp < JT3  (nominal temperature range 2)
+// - 0x10: JT3  < Temp < JT4  (high, but okay temperature)
+// - 0x20: JT4  < Temp (above maximum operating temperature)
+pub static TEMPERATURE: Packet = Packet {
+    name: "temperature",
+    msg_type: MessageType::Temperature,
+    subtype: 0,
+    fields: &[
+        number("eps_mb_temp", "EPS", "mb_BoardTemperature", I8),
+        number("eps_db_temp", "EPS", "db_BoardTemperature", I8),
+        number("eps_bcr2a_temp", "EPS", "mb_ArrayTempSa2a", I8),
+        number("eps_bcr2b_temp", "EPS", "mb_ArrayTempSa2b", I8),
+        number("eps_bcr8a_temp", "EPS", "db_ArrayTempSa8a", I8),
+        number("eps_bcr8b_temp", "EPS", "db_ArrayTempSa8b", I8),
+        number("eps_bcr9a_temp", "EPS", "db_ArrayTempSa9a", I8),
+        number("eps_bcr9b_temp", "EPS", "db_ArrayTempSa9b", I8),
+        number("mai_gyro_temp", "MAI400", "rawImu_gyroTemp", I8),
+        // Already converted to *C by telem-app
+        number("mai_motor_temp", "MAI400", "rwsMotorTemp_eng", I8),
+        // The BIM reports *K
+        number("bim_temp0", "bim", "temp0", I8).scaled(1.0, -273.15),
+        number("bim_temp1", "bim", "temp1", I8).scaled(1.0, -273.15),
+        number("bim_temp2", "bim", "temp2", I8).scaled(1.0, -273.15),
+        number("bim_temp3", "bim", "temp3", I8).scaled(1.0, -273.15),
+        number("bim_temp4", "bim", "temp4", I8).scaled(1.0, -273.15),
+        number("bim_temp5", "bim", "temp5", I8).scaled(1.0, -273.15),
+        // The BM2 reports 0.1*K for its internal sensor and 0.1*C for the external ones
+        number("bm2_temp", "bm2", "temperature", I8).scaled(0.1, -273.15),
+        number("bm2_ts1_temp", "bm2", "ts1_temp", I8).scaled(0.1, 0.0),
+        number("bm2_ts2_temp", "bm2", "ts2_temp", I8).scaled(0.1, 0.0),
+        number("bm2_temp_range", "bm2", "temp_range", U8).masked(0x3F),
+    ],
+};
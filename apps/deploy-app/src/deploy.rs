@@ -19,6 +19,7 @@ use failure::{bail, Error};
 use kubos_app::*;
 use kubos_system::*;
 use log::*;
+use power_state::Unit;
 use std::process::Command;
 use std::thread;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
@@ -92,23 +93,15 @@ pub fn try_deploy(force: bool) -> Result<(), Error> {
 }
 
 fn start_radios() {
-    let mcu_service = ServiceConfig::new("pumpkin-mcu-service");
-
-    // Turn on radios:
+    // Turn on radios. These can't be commanded off, so this always powers them:
     // Duplex radio
-    if let Err(error) = query(&mcu_service, DUPLEX_POWER, Some(QUERY_TIMEOUT)) {
+    if let Err(error) = power_state::ensure(Unit::Duplex) {
         error!("Failed to turn on duplex radio: {:?}", error);
     };
     // Simplex radio
-    if let Err(error) = query(&mcu_service, SIMPLEX_POWER, Some(QUERY_TIMEOUT)) {
+    if let Err(error) = power_state::ensure(Unit::Simplex) {
         error!("Failed to turn on simplex radio: {:?}", error);
     };
-    if let Err(error) = query(&mcu_service, SIMPLEX_COMM, Some(QUERY_TIMEOUT)) {
-        error!("Failed to set simplex UART: {:?}", error);
-    }
-    if let Err(error) = query(&mcu_service, SIMPLEX_PASS, Some(QUERY_TIMEOUT)) {
-        error!("Failed to enable simplex communication: {:?}", error);
-    };
 
     // Start transmitting H&S beacon
     let app_service = ServiceConfig::new("app-service");
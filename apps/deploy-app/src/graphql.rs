@@ -20,62 +20,6 @@ use std::time::Duration;
 
 pub const QUERY_TIMEOUT: Duration = Duration::from_millis(500);
 
-// Turn on the MAI-400
-pub const MAI_POWER: &str = r#"
-    mutation {
-        passthrough(module: "aim2", command: "AIM:ADCS:POW ON") {
-            status
-        }
-    }
-"#;
-
-// Configure the MAI-400's UART
-// BBB UART5 = CSK UART0
-pub const MAI_COMM: &str = r#"
-    mutation {
-        passthrough(module: "aim2", command: "AIM:ADCS:COMM UART0") {
-            status
-        }
-    }
-"#;
-
-// Enable communication with the MAI-400
-pub const MAI_PASS: &str = r#"
-    mutation {
-        passthrough(module: "aim2", command: "AIM:ADCS:PASS ON") {
-            status
-        }
-    }
-"#;
-
-// Turn on the OEM7
-pub const OEM_POWER: &str = r#"
-    mutation {
-        passthrough(module: "aim2", command: "GPS:POW ON") {
-            status
-        }
-    }
-"#;
-
-// Configure the OEM's UART
-// BBB UART4 = CSK UART3
-pub const OEM_COMM: &str = r#"
-    mutation {
-        passthrough(module: "aim2", command: "GPS:COMM UART3") {
-            status
-        }
-    }
-"#;
-
-// Enable communication with the OEM
-pub const OEM_PASS: &str = r#"
-    mutation {
-        passthrough(module: "aim2", command: "GPS:PASS ON") {
-            status
-        }
-    }
-"#;
-
 // TODO: How frequently do we want the OEM to send us position data?
 // Set up the OEM logs that we care about
 pub const OEM_SET_LOGS: &str = r#"
@@ -91,44 +35,6 @@ pub const OEM_SET_LOGS: &str = r#"
     }
 "#;
 
-// Turn on the duplex radio
-pub const DUPLEX_POWER: &str = r#"
-    mutation {
-        passthrough(module: "bim", command: "BIM:UART:POW 2,ON") {
-            status
-        }
-    }
-"#;
-
-// Turn on the simplex radio
-pub const SIMPLEX_POWER: &str = r#"
-    mutation {
-        passthrough(module: "rhm", command: "RHM:GS:POW ON") {
-            status
-        }
-    }
-"#;
-
-// Configure the simplex's communication port
-// Note: The simplex is connected to the stack via the RHM's I2C bus, not via a direct UART
-// connection
-pub const SIMPLEX_COMM: &str = r#"
-    mutation {
-        passthrough(module: "rhm", command: "RHM:GS:COMM I2C") {
-            status
-        }
-    }
-"#;
-
-// Enable simplex communication
-pub const SIMPLEX_PASS: &str = r#"
-    mutation {
-        passthrough(module: "rhm", command: "RHM:GS:PASS ON") {
-            status
-        }
-    }
-"#;
-
 // Kick off the H&S beacon application
 pub const START_BEACON: &str = r#"
     mutation {
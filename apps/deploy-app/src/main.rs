@@ -23,6 +23,7 @@ use crate::graphql::*;
 use failure::Error;
 use kubos_app::*;
 use log::*;
+use power_state::Unit;
 use std::thread;
 
 struct MyApp;
@@ -33,9 +34,8 @@ impl AppHandler for MyApp {
         let deploy_handle = thread::spawn(deploy);
 
         // TODO: Maybe just move GPS/ADCS initialization into their housekeeping apps
-        let mcu_service = ServiceConfig::new("pumpkin-mcu-service");
-        init_gps(&mcu_service);
-        init_adcs(&mcu_service);
+        init_gps();
+        init_adcs();
 
         // Wait for deployment to finish before exiting
         if let Err(error) = deploy_handle.join() {
@@ -59,17 +59,16 @@ impl AppHandler for MyApp {
     }
 }
 
-fn init_gps(mcu_service: &ServiceConfig) {
-    // Turn on GPS
-    if let Err(error) = query(mcu_service, OEM_POWER, Some(QUERY_TIMEOUT)) {
-        error!("Failed to turn on OEM: {:?}", error);
-    };
-    if let Err(error) = query(mcu_service, OEM_COMM, Some(QUERY_TIMEOUT)) {
-        error!("Failed to set OEM UART: {:?}", error);
+fn init_gps() {
+    // Turn on GPS, unless the ground has commanded it off
+    match power_state::ensure(Unit::Gps) {
+        Ok(true) => {}
+        Ok(false) => {
+            info!("OEM commanded off. Skipping initialization");
+            return;
+        }
+        Err(error) => error!("Failed to turn on OEM: {:?}", error),
     }
-    if let Err(error) = query(mcu_service, OEM_PASS, Some(QUERY_TIMEOUT)) {
-        error!("Failed to enable OEM communication: {:?}", error);
-    };
     // Set up OEM log messages that we do/don't want
     // (Position data + error messages)
     let oem_service = ServiceConfig::new("novatel-oem6-service");
@@ -94,17 +93,17 @@ fn init_gps(mcu_service: &ServiceConfig) {
     }
 }
 
-fn init_adcs(mcu_service: &ServiceConfig) {
-    // Turn on ADCS. It will automatically go into detumble mode
-    if let Err(error) = query(mcu_service, MAI_POWER, Some(QUERY_TIMEOUT)) {
-        error!("Failed to turn on MAI-400: {:?}", error);
-    };
-    if let Err(error) = query(mcu_service, MAI_COMM, Some(QUERY_TIMEOUT)) {
-        error!("Failed to set MAI-400 UART: {:?}", error);
+fn init_adcs() {
+    // Turn on ADCS, unless the ground has commanded it off. It will automatically go into
+    // detumble mode
+    match power_state::ensure(Unit::Adcs) {
+        Ok(true) => {}
+        Ok(false) => {
+            info!("MAI-400 commanded off. Skipping initialization");
+            return;
+        }
+        Err(error) => error!("Failed to turn on MAI-400: {:?}", error),
     }
-    if let Err(error) = query(mcu_service, MAI_PASS, Some(QUERY_TIMEOUT)) {
-        error!("Failed to enable MAI-400 communication: {:?}", error);
-    };
 
     // Kick off ADCS housekeeping app
     let app_service = ServiceConfig::new("app-service");
@@ -0,0 +1,127 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Conversions between the different ways time is reported
+//
+// - GPS week and milliseconds into the week (NovAtel OEM6)
+// - GPS seconds, since the GPS epoch (MAI-400 `gpsTime`, `orbitEpoch`)
+// - Unix time, in UTC (telemetry timestamps, the system clock)
+// - ISO-8601 (for people)
+//
+// GPS time started at 1980-01-06 00:00:00 UTC and, unlike UTC, doesn't include leap seconds, so it
+// has been drifting ahead of UTC ever since. All Unix times here are UTC, with leap seconds
+// taken into account.
+
+use chrono::{DateTime, TimeZone, Utc};
+
+// Unix time of the GPS epoch
+pub const GPS_EPOCH: f64 = 315_964_800.0;
+
+pub const SECONDS_PER_WEEK: f64 = 604_800.0;
+
+// The Unix time at which each leap second took effect, along with the total GPS-UTC offset from
+// then on. This needs to be updated whenever the IERS announces a new leap second
+const LEAP_SECONDS: &[(f64, f64)] = &[
+    (362_793_600.0, 1.0),    // 1981-07-01
+    (394_329_600.0, 2.0),    // 1982-07-01
+    (425_865_600.0, 3.0),    // 1983-07-01
+    (489_024_000.0, 4.0),    // 1985-07-01
+    (567_993_600.0, 5.0),    // 1988-01-01
+    (631_152_000.0, 6.0),    // 1990-01-01
+    (662_688_000.0, 7.0),    // 1991-01-01
+    (709_948_800.0, 8.0),    // 1992-07-01
+    (741_484_800.0, 9.0),    // 1993-07-01
+    (773_020_800.0, 10.0),   // 1994-07-01
+    (820_454_400.0, 11.0),   // 1996-01-01
+    (867_715_200.0, 12.0),   // 1997-07-01
+    (915_148_800.0, 13.0),   // 1999-01-01
+    (1_136_073_600.0, 14.0), // 2006-01-01
+    (1_230_768_000.0, 15.0), // 2009-01-01
+    (1_341_100_800.0, 16.0), // 2012-07-01
+    (1_435_708_800.0, 17.0), // 2015-07-01
+    (1_483_228_800.0, 18.0), // 2017-01-01
+];
+
+// Number of leap seconds between GPS time and UTC at the given GPS time
+pub fn leap_seconds_gps(gps_seconds: f64) -> f64 {
+    LEAP_SECONDS
+        .iter()
+        .rev()
+        .find(|(unix, offset)| gps_seconds >= unix - GPS_EPOCH + offset)
+        .map(|(_, offset)| *offset)
+        .unwrap_or(0.0)
+}
+
+// Number of leap seconds between GPS time and UTC at the given Unix time
+pub fn leap_seconds_unix(unix: f64) -> f64 {
+    LEAP_SECONDS
+        .iter()
+        .rev()
+        .find(|(start, _)| unix >= *start)
+        .map(|(_, offset)| *offset)
+        .unwrap_or(0.0)
+}
+
+// Convert a GPS week number and milliseconds into the week to GPS seconds
+pub fn week_ms_to_gps(week: f64, ms: f64) -> f64 {
+    week * SECONDS_PER_WEEK + ms / 1000.0
+}
+
+// Convert GPS seconds to a GPS week number and milliseconds into the week
+pub fn gps_to_week_ms(gps_seconds: f64) -> (u32, u32) {
+    let mut week = (gps_seconds / SECONDS_PER_WEEK).floor();
+    let mut ms = ((gps_seconds - week * SECONDS_PER_WEEK) * 1000.0).round();
+
+    // Anything which rounds up to the end of the week is really the start of the next one
+    if ms >= SECONDS_PER_WEEK * 1000.0 {
+        week += 1.0;
+        ms -= SECONDS_PER_WEEK * 1000.0;
+    }
+
+    (week as u32, ms as u32)
+}
+
+// Convert GPS seconds to Unix time
+pub fn gps_to_unix(gps_seconds: f64) -> f64 {
+    GPS_EPOCH + gps_seconds - leap_seconds_gps(gps_seconds)
+}
+
+// Convert Unix time to GPS seconds
+pub fn unix_to_gps(unix: f64) -> f64 {
+    unix - GPS_EPOCH + leap_seconds_unix(unix)
+}
+
+// Convert a GPS week number and milliseconds into the week to Unix time
+pub fn week_ms_to_unix(week: f64, ms: f64) -> f64 {
+    gps_to_unix(week_ms_to_gps(week, ms))
+}
+
+// Format Unix time as ISO-8601 (ex. "2019-10-04T15:30:00.000Z")
+pub fn unix_to_iso8601(unix: f64) -> Option<String> {
+    let secs = unix.floor();
+    let nanos = ((unix - secs) * 1_000_000_000.0) as u32;
+
+    Utc.timestamp_opt(secs as i64, nanos)
+        .single()
+        .map(|time| time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+}
+
+// Parse an ISO-8601 time, with any offset from UTC (ex. "2019-10-04T15:30:00Z"), into Unix time
+pub fn iso8601_to_unix(time: &str) -> Option<f64> {
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|time| time.timestamp() as f64 + f64::from(time.timestamp_subsec_nanos()) / 1e9)
+}
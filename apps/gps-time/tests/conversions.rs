@@ -0,0 +1,108 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use gps_time::*;
+
+// 2017-01-01, when GPS-UTC went from 17 to 18 seconds
+const LEAP_2017: f64 = 1_483_228_800.0;
+// The same moment in GPS seconds
+const LEAP_2017_GPS: f64 = LEAP_2017 - GPS_EPOCH + 18.0;
+
+#[test]
+fn leap_seconds_change_at_the_boundary() {
+    assert_eq!(leap_seconds_unix(LEAP_2017 - 1.0), 17.0);
+    assert_eq!(leap_seconds_unix(LEAP_2017), 18.0);
+
+    assert_eq!(leap_seconds_gps(LEAP_2017_GPS - 2.0), 17.0);
+    assert_eq!(leap_seconds_gps(LEAP_2017_GPS), 18.0);
+
+    // Before the first leap second, GPS time and UTC were in step
+    assert_eq!(leap_seconds_unix(GPS_EPOCH), 0.0);
+    assert_eq!(leap_seconds_gps(0.0), 0.0);
+    assert_eq!(leap_seconds_unix(362_793_600.0), 1.0);
+}
+
+#[test]
+fn converts_across_a_leap_second() {
+    assert_eq!(unix_to_gps(LEAP_2017 - 1.0), LEAP_2017_GPS - 2.0);
+    assert_eq!(unix_to_gps(LEAP_2017), LEAP_2017_GPS);
+
+    assert_eq!(gps_to_unix(LEAP_2017_GPS - 2.0), LEAP_2017 - 1.0);
+    assert_eq!(gps_to_unix(LEAP_2017_GPS), LEAP_2017);
+    // The leap second itself (23:59:60) has no Unix time of its own, so it shares one with the
+    // following second
+    assert_eq!(gps_to_unix(LEAP_2017_GPS - 1.0), LEAP_2017);
+}
+
+#[test]
+fn unix_and_gps_round_trip() {
+    for unix in &[
+        GPS_EPOCH,
+        362_793_599.0,
+        362_793_600.0,
+        LEAP_2017 - 0.5,
+        LEAP_2017,
+        LEAP_2017 + 0.5,
+        1_570_203_000.25,
+    ] {
+        assert_eq!(gps_to_unix(unix_to_gps(*unix)), *unix);
+    }
+}
+
+#[test]
+fn week_ms_round_trip() {
+    // GPS week 2048 started at 2019-04-07 00:00:00 GPS time, which is 18 seconds ahead of UTC
+    let unix = week_ms_to_unix(2048.0, 0.0);
+    assert_eq!(unix_to_iso8601(unix).unwrap(), "2019-04-06T23:59:42.000Z");
+    assert_eq!(gps_to_week_ms(unix_to_gps(unix)), (2048, 0));
+
+    for (week, ms) in &[(0, 0), (1930, 345_600_123), (2048, 604_799_999)] {
+        let gps = week_ms_to_gps(f64::from(*week), f64::from(*ms));
+        assert_eq!(gps_to_week_ms(gps), (*week, *ms));
+    }
+}
+
+#[test]
+fn end_of_week_rolls_over() {
+    // Less than half a millisecond before the end of the week rounds up to the next one
+    let gps = 2049.0 * SECONDS_PER_WEEK - 0.0002;
+    assert_eq!(gps_to_week_ms(gps), (2049, 0));
+
+    let gps = 2049.0 * SECONDS_PER_WEEK - 0.001;
+    assert_eq!(gps_to_week_ms(gps), (2048, 604_799_999));
+}
+
+#[test]
+fn iso8601_round_trip() {
+    let unix = iso8601_to_unix("2019-10-04T15:30:00.250Z").unwrap();
+    assert_eq!(unix, 1_570_203_000.25);
+    assert_eq!(unix_to_iso8601(unix).unwrap(), "2019-10-04T15:30:00.250Z");
+
+    // Offsets from UTC are taken into account
+    assert_eq!(iso8601_to_unix("2019-10-04T17:30:00.250+02:00"), Some(unix));
+
+    assert_eq!(iso8601_to_unix("2019-10-04 15:30"), None);
+}
+
+#[test]
+fn every_format_round_trips() {
+    let unix = iso8601_to_unix("2017-01-01T00:00:00Z").unwrap();
+    assert_eq!(unix, LEAP_2017);
+
+    let (week, ms) = gps_to_week_ms(unix_to_gps(unix));
+    let unix = week_ms_to_unix(f64::from(week), f64::from(ms));
+    assert_eq!(unix_to_iso8601(unix).unwrap(), "2017-01-01T00:00:00.000Z");
+}
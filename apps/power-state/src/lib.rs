@@ -0,0 +1,283 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Shared power-state manager for the units controlled through the Pumpkin Sup MCU modules
+//
+// The ground decides whether each unit should be on or off. That decision is saved to disk, so
+// it survives reboots, and every app which touches the hardware checks it before doing anything:
+//
+// - `commanded` - Get the state the ground last requested for a unit
+// - `command` - Record a new requested state and immediately apply it
+// - `ensure` - Make sure a unit which should be on actually is. Units which are commanded off
+//              are left alone
+//
+// The radios can't be commanded. The duplex is our only uplink, so a saved "off" would lock the
+// ground out for good, and any such line left in the state file is ignored. They're always on.
+//
+// Units' power states aren't read back from their Sup MCU modules, so power-on commands are sent
+// once each time an app starts, and again whenever the app finds that a unit has stopped
+// responding (see `mark_off`).
+//
+// The location of the state file can be changed in the system's config.toml file:
+//
+// [power-state]
+// path = "/home/system/kubos/power-state"
+
+use failure::{bail, Error};
+use kubos_app::{query, ServiceConfig};
+use log::*;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+const DEFAULT_PATH: &str = "/home/system/kubos/power-state";
+const QUERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Unit {
+    // NovAtel OEM6, through the AIM2
+    Gps,
+    // MAI-400, through the AIM2
+    Adcs,
+    // BIM temperature sensors
+    BimTemp,
+    // NSL duplex radio, on the BIM's UART 2
+    Duplex,
+    // NSL simplex radio, through the RHM
+    Simplex,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PowerState {
+    On,
+    Off,
+}
+
+impl fmt::Display for PowerState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PowerState::On => write!(f, "on"),
+            PowerState::Off => write!(f, "off"),
+        }
+    }
+}
+
+// How to control a unit, and how to tell whether it's on
+struct Control {
+    module: &'static str,
+    // Commands to turn the unit on and open up communication with it, in order
+    on: &'static [&'static str],
+    off: &'static str,
+}
+
+impl Unit {
+    // Units the ground can command. The radios are deliberately left out
+    pub const ALL: [Unit; 3] = [Unit::Gps, Unit::Adcs, Unit::BimTemp];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Unit::Gps => "gps",
+            Unit::Adcs => "adcs",
+            Unit::BimTemp => "bim_temp",
+            Unit::Duplex => "duplex",
+            Unit::Simplex => "simplex",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Unit> {
+        Unit::ALL.iter().cloned().find(|unit| unit.name() == name)
+    }
+
+    fn control(self) -> Control {
+        match self {
+            Unit::Gps => Control {
+                module: "aim2",
+                // BBB UART4 = CSK UART3
+                on: &["GPS:POW ON", "GPS:COMM UART3", "GPS:PASS ON"],
+                off: "GPS:POW OFF",
+            },
+            Unit::Adcs => Control {
+                module: "aim2",
+                // BBB UART5 = CSK UART0
+                on: &["AIM:ADCS:POW ON", "AIM:ADCS:COMM UART0", "AIM:ADCS:PASS ON"],
+                off: "AIM:ADCS:POW OFF",
+            },
+            Unit::BimTemp => Control {
+                module: "bim",
+                on: &["BIM:TEMP:POW ON"],
+                off: "BIM:TEMP:POW OFF",
+            },
+            Unit::Duplex => Control {
+                module: "bim",
+                on: &["BIM:UART:POW 2,ON"],
+                off: "BIM:UART:POW 2,OFF",
+            },
+            Unit::Simplex => Control {
+                module: "rhm",
+                // The simplex is connected to the stack via the RHM's I2C bus, not via a direct
+                // UART connection
+                on: &["RHM:GS:POW ON", "RHM:GS:COMM I2C", "RHM:GS:PASS ON"],
+                off: "RHM:GS:POW OFF",
+            },
+        }
+    }
+}
+
+// Units which this app has already turned on
+static POWERED: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+fn state_path() -> String {
+    ServiceConfig::new("power-state")
+        .get("path")
+        .and_then(|val| val.as_str().map(|path| path.to_owned()))
+        .unwrap_or_else(|| DEFAULT_PATH.to_owned())
+}
+
+// Read the saved states. The file has one "<unit> <on|off>" line per unit which has been
+// commanded. Units which have never been commanded default to on
+fn load() -> Vec<(Unit, PowerState)> {
+    let contents = fs::read_to_string(state_path()).unwrap_or_default();
+
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let unit = Unit::from_name(fields.next()?)?;
+            let state = match fields.next()? {
+                "on" => PowerState::On,
+                "off" => PowerState::Off,
+                _ => return None,
+            };
+            Some((unit, state))
+        })
+        .collect()
+}
+
+fn save(states: &[(Unit, PowerState)]) -> Result<(), Error> {
+    let path = state_path();
+
+    // Write to a temporary file first, so we never end up with a half-written state file
+    let temp_path = format!("{}.tmp", path);
+    {
+        let mut file = File::create(&temp_path)?;
+        for (unit, state) in states {
+            writeln!(file, "{} {}", unit.name(), state)?;
+        }
+        file.sync_all()?;
+    }
+    fs::rename(&temp_path, &path)?;
+
+    Ok(())
+}
+
+// Get the state the ground last requested for a unit
+pub fn commanded(unit: Unit) -> PowerState {
+    load()
+        .into_iter()
+        .find(|(saved, _)| *saved == unit)
+        .map(|(_, state)| state)
+        .unwrap_or(PowerState::On)
+}
+
+// Record the state the ground wants a unit to be in, and apply it
+pub fn command(unit: Unit, state: PowerState) -> Result<(), Error> {
+    if !Unit::ALL.contains(&unit) {
+        bail!("{} power can't be commanded", unit.name());
+    }
+
+    let mut states: Vec<(Unit, PowerState)> = load()
+        .into_iter()
+        .filter(|(saved, _)| *saved != unit)
+        .collect();
+    states.push((unit, state));
+    save(&states)?;
+
+    info!("{} commanded {}", unit.name(), state);
+
+    let control = unit.control();
+    let mut powered = POWERED.lock().unwrap_or_else(PoisonError::into_inner);
+    match state {
+        PowerState::On => {
+            power_on(&control)?;
+            powered.insert(unit.name());
+        }
+        PowerState::Off => {
+            passthrough(control.module, control.off)?;
+            powered.remove(unit.name());
+        }
+    }
+
+    Ok(())
+}
+
+// Make sure a unit is powered and ready to use, if it's supposed to be.
+// Returns `false` if the unit has been commanded off, in which case it should be left alone
+pub fn ensure(unit: Unit) -> Result<bool, Error> {
+    if commanded(unit) == PowerState::Off {
+        debug!("{} is commanded off", unit.name());
+        return Ok(false);
+    }
+
+    let mut powered = POWERED.lock().unwrap_or_else(PoisonError::into_inner);
+
+    if !powered.contains(unit.name()) {
+        info!("{} should be on. Turning it on", unit.name());
+        power_on(&unit.control())?;
+        powered.insert(unit.name());
+    }
+
+    Ok(true)
+}
+
+// Note that a unit has been found to be off (ex. it's stopped responding), so that it gets
+// turned back on the next time `ensure` is called
+pub fn mark_off(unit: Unit) {
+    POWERED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .remove(unit.name());
+}
+
+fn power_on(control: &Control) -> Result<(), Error> {
+    for command in control.on {
+        passthrough(control.module, command)?;
+    }
+    Ok(())
+}
+
+fn passthrough(module: &str, command: &str) -> Result<(), Error> {
+    let service = ServiceConfig::new("pumpkin-mcu-service");
+
+    let request = format!(
+        r#"mutation {{
+            passthrough(module: "{}", command: "{}") {{
+                status,
+                command
+            }}
+        }}"#,
+        module, command
+    );
+
+    let result = query(&service, &request, Some(QUERY_TIMEOUT))?;
+    if result["passthrough"]["status"].as_bool() != Some(true) {
+        bail!("Passthrough command failed: {}", command);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,279 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Selective and burst collection, requested through the app's `on_command` arguments
+//
+// telem-app [collector...] [--rate <Hz>] [--duration <seconds>]
+//
+// With no collectors listed, every collector is run. Without `--rate` or `--duration`, each
+// collector is only run once. Otherwise the collectors are sampled at the requested rate
+// (default: 1 Hz) until the requested duration (default: 60 seconds) has passed.
+//
+// Bursts can generate a lot of telemetry very quickly, so they're limited by the `[telem-app]`
+// section of the config file:
+//
+// [telem-app]
+// burst_max_rate = 10       # Hz
+// burst_max_duration = 3600 # Seconds
+// burst_max_disk = 70       # Percent of the telemetry database's partition which may be in use
+//
+// The disk usage is checked before and throughout a burst, and the burst is stopped early if it
+// goes above the limit.
+
+use crate::collector::Collector;
+use crate::schedule::{self, Schedule};
+use failure::{bail, Error};
+use kubos_app::ServiceConfig;
+use log::*;
+use std::process::Command;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_RATE: f64 = 1.0;
+const DEFAULT_DURATION: u64 = 60;
+const DEFAULT_MAX_RATE: f64 = 10.0;
+const DEFAULT_MAX_DURATION: u64 = 3600;
+// Stay well under the point where obc-hs starts deleting old telemetry (80%)
+const DEFAULT_MAX_DISK: u8 = 70;
+// How often to re-check the disk usage during a burst
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub struct Burst {
+    // Samples per second
+    pub rate: f64,
+    pub duration: Duration,
+}
+
+#[derive(Debug)]
+pub struct Request {
+    // Names of the collectors to run. Empty if all of them should be run
+    pub collectors: Vec<String>,
+    pub burst: Option<Burst>,
+}
+
+impl Request {
+    pub fn parse(args: &[String]) -> Result<Request, Error> {
+        let mut collectors = vec![];
+        let mut rate = None;
+        let mut duration = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--rate" => {
+                    let value = args.next().and_then(|val| val.parse::<f64>().ok());
+                    match value {
+                        Some(val) if val > 0.0 => rate = Some(val),
+                        _ => bail!("--rate requires a positive number of samples per second"),
+                    }
+                }
+                "--duration" => {
+                    let value = args.next().and_then(|val| val.parse::<u64>().ok());
+                    match value {
+                        Some(val) if val > 0 => duration = Some(val),
+                        _ => bail!("--duration requires a positive number of seconds"),
+                    }
+                }
+                other if other.starts_with("--") => bail!("Unknown option: {}", other),
+                name => collectors.push(name.to_owned()),
+            }
+        }
+
+        let burst = if rate.is_some() || duration.is_some() {
+            Some(Burst {
+                rate: rate.unwrap_or(DEFAULT_RATE),
+                duration: Duration::from_secs(duration.unwrap_or(DEFAULT_DURATION)),
+            })
+        } else {
+            None
+        };
+
+        Ok(Request { collectors, burst })
+    }
+
+    // Pick out the requested collectors from the registry
+    pub fn select(
+        &self,
+        registry: Vec<Arc<dyn Collector>>,
+    ) -> Result<Vec<Arc<dyn Collector>>, Error> {
+        if self.collectors.is_empty() {
+            return Ok(registry);
+        }
+
+        for name in self.collectors.iter() {
+            if !registry.iter().any(|collector| collector.name() == name) {
+                bail!("Unknown collector: {}", name);
+            }
+        }
+
+        Ok(registry
+            .into_iter()
+            .filter(|collector| self.collectors.iter().any(|name| collector.name() == name))
+            .collect())
+    }
+}
+
+// Run each of the collectors once
+pub fn run_once(collectors: Vec<Arc<dyn Collector>>) {
+    for collector in collectors {
+        let schedule = Schedule::load(collector.name(), collector.default_schedule());
+        let _ = schedule::run_once(collector, schedule.timeout);
+    }
+}
+
+// Sample the collectors at the requested rate until the burst is over
+pub fn run(collectors: Vec<Arc<dyn Collector>>, burst: Burst) -> Result<(), Error> {
+    let config = ServiceConfig::new("telem-app");
+    let max_rate = config
+        .get("burst_max_rate")
+        .and_then(|val| {
+            val.as_float()
+                .or_else(|| val.as_integer().map(|val| val as f64))
+        })
+        .unwrap_or(DEFAULT_MAX_RATE);
+    let max_duration = config
+        .get("burst_max_duration")
+        .and_then(|val| val.as_integer())
+        .map(|val| Duration::from_secs(val as u64))
+        .unwrap_or_else(|| Duration::from_secs(DEFAULT_MAX_DURATION));
+    let max_disk = config
+        .get("burst_max_disk")
+        .and_then(|val| val.as_integer())
+        .map(|val| val as u8)
+        .unwrap_or(DEFAULT_MAX_DISK);
+
+    let rate = if burst.rate > max_rate {
+        warn!(
+            "Burst rate {} Hz is too high. Limiting to {} Hz",
+            burst.rate, max_rate
+        );
+        max_rate
+    } else {
+        burst.rate
+    };
+
+    let duration = if burst.duration > max_duration {
+        warn!(
+            "Burst duration {:?} is too long. Limiting to {:?}",
+            burst.duration, max_duration
+        );
+        max_duration
+    } else {
+        burst.duration
+    };
+
+    let usage = disk_usage();
+    if usage >= max_disk {
+        bail!(
+            "Disk usage too high for a burst: {}% (limit {}%)",
+            usage,
+            max_disk
+        );
+    }
+
+    info!(
+        "Starting {} Hz burst collection for {:?}: {:?}",
+        rate,
+        duration,
+        collectors
+            .iter()
+            .map(|collector| collector.name())
+            .collect::<Vec<&str>>()
+    );
+
+    let period = Duration::from_secs_f64(1.0 / rate);
+    let start = Instant::now();
+    let mut last_disk_check = start;
+    let mut cycles = 0;
+
+    // Results channel for each collector's last collection, if it timed out and hasn't finished
+    // yet
+    let mut pending: Vec<Option<Receiver<Result<(), Error>>>> =
+        collectors.iter().map(|_| None).collect();
+
+    while start.elapsed() < duration {
+        let cycle = Instant::now();
+
+        if cycle.duration_since(last_disk_check) >= DISK_CHECK_INTERVAL {
+            last_disk_check = cycle;
+            let usage = disk_usage();
+            if usage >= max_disk {
+                error!(
+                    "Disk usage too high: {}% (limit {}%). Stopping burst collection",
+                    usage, max_disk
+                );
+                break;
+            }
+        }
+
+        for (collector, pending) in collectors.iter().zip(pending.iter_mut()) {
+            // Don't pile more requests onto a subsystem which hasn't answered the last one
+            if let Some(Err(TryRecvError::Empty)) = pending.as_ref().map(|rx| rx.try_recv()) {
+                continue;
+            }
+
+            // Collectors are run one after another, so if they take longer than the requested
+            // period, we'll just sample as fast as they allow
+            let schedule = Schedule::load(collector.name(), collector.default_schedule());
+            *pending = schedule::run_once(collector.clone(), schedule.timeout);
+        }
+
+        cycles += 1;
+
+        if let Some(remaining) = period.checked_sub(cycle.elapsed()) {
+            thread::sleep(remaining);
+        }
+    }
+
+    info!(
+        "Burst collection finished after {:?}: {} cycles",
+        start.elapsed(),
+        cycles
+    );
+
+    Ok(())
+}
+
+// Get the percentage of the telemetry database's partition which is in use.
+// If we can't tell, we assume the worst
+fn disk_usage() -> u8 {
+    let database = ServiceConfig::new("telemetry-service")
+        .get("database")
+        .and_then(|val| val.as_str().map(|path| path.to_owned()))
+        .unwrap_or_else(|| "/home".to_owned());
+
+    let output = match Command::new("df").arg(&database).output() {
+        Ok(output) => output,
+        Err(error) => {
+            error!("Failed to get current disk usage info: {:?}", error);
+            return 100;
+        }
+    };
+
+    // The last line of the output is the partition's usage, with the percent in use as the
+    // second to last entry:
+    // /dev/mmcblk0p4  3558576  157136  3201416   5% /home
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .last()
+        .and_then(|line| line.split_whitespace().rev().nth(1))
+        .and_then(|percent| percent.trim_end_matches('%').parse::<u8>().ok())
+        .unwrap_or(100)
+}
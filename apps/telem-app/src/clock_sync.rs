@@ -0,0 +1,553 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Discipline the OBC's clock with GPS time
+//
+// Everything with a timestamp relies on the OBC's clock, which isn't kept across a power cycle.
+// Once the OEM6 knows what time it is, we compare its time against the system clock and either
+// slew the clock (for small corrections) or step it (for large ones).
+//
+// The OEM's time comes from its most recent position log, which deploy-app configures to be
+// sent once a minute. To know how old that time is, we poll the receiver until a new log shows
+// up, and use the moment it arrived as our reference. Polling quickly for a whole minute would
+// swamp the OEM6 service, so we first poll slowly to find out roughly when the logs arrive, and
+// then only poll quickly around the time the next one is due. A failed query just costs us
+// that poll, unless the service stops answering altogether.
+//
+// The log's time is the time of its position solution, not the time it reached us. The receiver
+// has to compute the solution, send the log over the serial port, and the service has to parse
+// it before we can see it. That delay is `latency` seconds, give or take `latency_uncertainty`
+// seconds, and is added to the log's time (and to the uncertainty of the result).
+//
+// Corrections are refused if:
+//
+// - The receiver's time status isn't at least `min_time_status` (see `oem6::time_status_code`)
+// - The GPS time is before the mission could possibly have launched
+// - The clock has already been synced by this app, and the correction is larger than
+//   `max_jump` seconds. A clock which was correct a few minutes ago can't have drifted that far,
+//   so it's much more likely that the receiver is wrong
+
+use crate::collector::Collector;
+use crate::events::{event, EVENT_SUBSYSTEM};
+use crate::oem6::time_status_code;
+use crate::point::TelemPoint;
+use crate::schedule::Schedule;
+use crate::telem_db::{send_telem, timestamp_now};
+use failure::{bail, format_err, Error};
+use kubos_app::*;
+use log::*;
+use power_state::{PowerState, Unit};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const OEM_TIME: &str = r#"{
+    lockStatus {
+        time {
+            ms,
+            week
+        },
+        timeStatus
+    }
+}"#;
+
+// FINE
+const DEFAULT_MIN_TIME_STATUS: i64 = 160;
+// Corrections smaller than this many seconds are slewed rather than stepped
+const DEFAULT_SLEW_LIMIT: f64 = 0.5;
+const DEFAULT_MAX_JUMP: f64 = 10.0;
+// Seconds to wait for a new position log. We need to see two of them: one to find out when
+// they arrive, and one to measure
+const DEFAULT_SYNC_WINDOW: u64 = 130;
+// Seconds from a position solution to its log showing up in the OEM6 service, and how far off
+// that could be. A rough allowance for the receiver sending the log over its serial port and the
+// service parsing it
+const DEFAULT_LATENCY: f64 = 0.05;
+const DEFAULT_LATENCY_UNCERTAINTY: f64 = 0.05;
+
+// Seconds between position logs (see `OEM_SET_LOGS` in deploy-app)
+const LOG_INTERVAL: f64 = 60.0;
+// How often to poll while finding out roughly when the logs arrive, and then while waiting for
+// the next one
+const COARSE_POLL: Duration = Duration::from_secs(1);
+const FINE_POLL: Duration = Duration::from_millis(100);
+// Start polling quickly this many seconds before the next log is due
+const FINE_LEAD: f64 = 2.0;
+// Give up after this many failed queries in a row
+const MAX_QUERY_ERRORS: u32 = 5;
+
+// 2019-01-01 00:00:00 UTC. No earlier time can be correct
+const MIN_PLAUSIBLE: f64 = 1_546_300_800.0;
+
+// Whether the clock has been set since the app started
+static SYNCED: AtomicBool = AtomicBool::new(false);
+
+struct Config {
+    min_time_status: i64,
+    slew_limit: f64,
+    max_jump: f64,
+    sync_window: Duration,
+    latency: f64,
+    latency_uncertainty: f64,
+}
+
+impl Config {
+    fn load() -> Config {
+        let config = ServiceConfig::new("telem-app").get("clock_sync");
+        let get = |key: &str| config.as_ref().and_then(|config| config.get(key).cloned());
+
+        Config {
+            min_time_status: get("min_time_status")
+                .and_then(|val| val.as_integer())
+                .unwrap_or(DEFAULT_MIN_TIME_STATUS),
+            slew_limit: get("slew_limit")
+                .and_then(|val| val.as_float())
+                .unwrap_or(DEFAULT_SLEW_LIMIT),
+            max_jump: get("max_jump")
+                .and_then(|val| val.as_float())
+                .unwrap_or(DEFAULT_MAX_JUMP),
+            sync_window: Duration::from_secs(
+                get("sync_window")
+                    .and_then(|val| val.as_integer())
+                    .map(|val| val.max(0) as u64)
+                    .unwrap_or(DEFAULT_SYNC_WINDOW),
+            ),
+            latency: get("latency")
+                .and_then(|val| val.as_float())
+                .unwrap_or(DEFAULT_LATENCY),
+            latency_uncertainty: get("latency_uncertainty")
+                .and_then(|val| val.as_float())
+                .unwrap_or(DEFAULT_LATENCY_UNCERTAINTY),
+        }
+    }
+}
+
+pub struct ClockSync;
+
+impl Collector for ClockSync {
+    fn name(&self) -> &str {
+        "clock_sync"
+    }
+
+    fn subsystem(&self) -> &str {
+        "CLOCK"
+    }
+
+    fn default_schedule(&self) -> Schedule {
+        Schedule::new(600, 150)
+    }
+
+    // The OEM6 collector keeps the GPS powered unless the ground has commanded it off, in which
+    // case there's nothing to sync against
+    fn prepare(&self) -> Result<bool, Error> {
+        Ok(power_state::commanded(Unit::Gps) == PowerState::On)
+    }
+
+    fn query(&self, telem_vec: &mut Vec<TelemPoint>) -> Result<(), Error> {
+        let config = Config::load();
+
+        let (status, solution, local, arrival_uncertainty) = wait_for_log(config.sync_window)?;
+
+        // What the GPS time was when the log arrived
+        let gps = solution + config.latency;
+        let uncertainty = arrival_uncertainty + config.latency_uncertainty;
+
+        let timestamp = timestamp_now();
+        let correction = gps - local;
+
+        telem_vec.push(TelemPoint::new("time_status", status, timestamp));
+        telem_vec.push(TelemPoint::new("correction", correction, timestamp));
+        telem_vec.push(TelemPoint::new("uncertainty", uncertainty, timestamp));
+
+        let action = decide(
+            &config,
+            SYNCED.load(Ordering::Relaxed),
+            status,
+            gps,
+            correction,
+            uncertainty,
+        );
+
+        match &action {
+            Action::Refuse(reason) => send_telem(
+                EVENT_SUBSYSTEM,
+                vec![event(
+                    "clock_sync_refused",
+                    format!("Not syncing clock to GPS: {}", reason),
+                    timestamp,
+                )],
+            ),
+            Action::Keep => SYNCED.store(true, Ordering::Relaxed),
+            Action::Slew => {
+                slew(correction)?;
+                SYNCED.store(true, Ordering::Relaxed);
+            }
+            Action::Step => {
+                step(correction)?;
+                SYNCED.store(true, Ordering::Relaxed);
+                send_telem(
+                    EVENT_SUBSYSTEM,
+                    vec![event(
+                        "clock_step",
+                        format!("Stepped clock by {:.3}s to match GPS", correction),
+                        timestamp_now(),
+                    )],
+                );
+            }
+        }
+
+        telem_vec.push(TelemPoint::new("action", action.name(), timestamp));
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    // Leave the clock alone, for this reason
+    Refuse(String),
+    // The clock is already as close as we can tell
+    Keep,
+    Slew,
+    Step,
+}
+
+impl Action {
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Refuse(_) => "refused",
+            Action::Keep => "none",
+            Action::Slew => "slew",
+            Action::Step => "step",
+        }
+    }
+}
+
+// Decide what to do about a `correction` second difference between the system clock and GPS
+// time. `synced` is whether the clock has already been synced since the app started
+fn decide(
+    config: &Config,
+    synced: bool,
+    status: i64,
+    gps: f64,
+    correction: f64,
+    uncertainty: f64,
+) -> Action {
+    if status < config.min_time_status {
+        Action::Refuse(format!("time status {} too low", status))
+    } else if gps < MIN_PLAUSIBLE {
+        Action::Refuse(format!("GPS time {:.3} is before the mission", gps))
+    } else if synced && correction.abs() > config.max_jump {
+        Action::Refuse(format!("{:.3}s correction is too large", correction))
+    } else if correction.abs() <= uncertainty {
+        // We can't do any better than this
+        Action::Keep
+    } else if correction.abs() <= config.slew_limit {
+        Action::Slew
+    } else {
+        Action::Step
+    }
+}
+
+// A new position log, and the system times of the polls either side of its arrival
+struct Arrival {
+    lock_status: serde_json::Value,
+    before: f64,
+    after: f64,
+    at: Instant,
+}
+
+// Poll the OEM until a new position log arrives.
+// Returns the log's time status code, its solution time (as Unix time), the system time when it
+// arrived, and how far off that arrival time could be
+fn wait_for_log(window: Duration) -> Result<(i64, f64, f64, f64), Error> {
+    let service = ServiceConfig::new("novatel-oem6-service");
+    let deadline = Instant::now() + window;
+    let mut errors = 0;
+
+    let mut read = || -> Result<Option<serde_json::Value>, Error> {
+        match query(&service, OEM_TIME, Some(Duration::from_secs(1))) {
+            Ok(result) => {
+                errors = 0;
+                Ok(Some(result["lockStatus"].clone()))
+            }
+            Err(error) => {
+                errors += 1;
+                if errors >= MAX_QUERY_ERRORS {
+                    bail!("OEM not responding: {}", error);
+                }
+                debug!("Failed to get OEM time: {}", error);
+                Ok(None)
+            }
+        }
+    };
+
+    // Find out roughly when the logs arrive
+    let coarse = watch(&mut read, COARSE_POLL, deadline)?;
+
+    // And then watch closely for the next one
+    let due = coarse.at + Duration::from_secs_f64(LOG_INTERVAL - FINE_LEAD);
+    let now = Instant::now();
+    if due > now {
+        thread::sleep(due.min(deadline) - now);
+    }
+    let fine = watch(&mut read, FINE_POLL, deadline)?;
+
+    let lock_status = &fine.lock_status;
+    let status = lock_status["timeStatus"]
+        .as_str()
+        .ok_or_else(|| format_err!("Missing time status"))?;
+    let code =
+        time_status_code(status).ok_or_else(|| format_err!("Unknown time status: {}", status))?;
+    let week = lock_status["time"]["week"]
+        .as_f64()
+        .ok_or_else(|| format_err!("Missing GPS week"))?;
+    let ms = lock_status["time"]["ms"]
+        .as_f64()
+        .ok_or_else(|| format_err!("Missing GPS milliseconds"))?;
+
+    // The log arrived sometime between the two polls
+    Ok((
+        i64::from(code),
+        gps_time::week_ms_to_unix(week, ms),
+        (fine.before + fine.after) / 2.0,
+        (fine.after - fine.before) / 2.0,
+    ))
+}
+
+// Poll every `interval` until the position log changes from the one seen by the first poll
+fn watch<F>(read: &mut F, interval: Duration, deadline: Instant) -> Result<Arrival, Error>
+where
+    F: FnMut() -> Result<Option<serde_json::Value>, Error>,
+{
+    let mut first = None;
+    let mut last_poll = None;
+
+    while Instant::now() < deadline {
+        let lock_status = read()?;
+        let now = timestamp_now();
+
+        if let Some(lock_status) = lock_status {
+            let time = (
+                lock_status["time"]["week"].as_f64(),
+                lock_status["time"]["ms"].as_f64(),
+            );
+
+            if let (Some(week), Some(ms)) = time {
+                match first {
+                    None => first = Some((week, ms)),
+                    Some(seen) if seen != (week, ms) => match last_poll {
+                        Some(before) => {
+                            return Ok(Arrival {
+                                lock_status,
+                                before,
+                                after: now,
+                                at: Instant::now(),
+                            });
+                        }
+                        // It arrived while we couldn't see it, so we don't know when. Wait for
+                        // the next one
+                        None => first = Some((week, ms)),
+                    },
+                    _ => {}
+                }
+            }
+
+            last_poll = Some(now);
+        } else {
+            // We don't know when the log could have arrived, so start timing from the next
+            // successful poll
+            last_poll = None;
+        }
+
+        thread::sleep(interval);
+    }
+
+    bail!("No new position log from the OEM before the sync window ended");
+}
+
+// Gradually speed up or slow down the clock until it's been adjusted by `correction` seconds
+fn slew(correction: f64) -> Result<(), Error> {
+    let delta = timeval(correction);
+
+    if unsafe { libc::adjtime(&delta, std::ptr::null_mut()) } != 0 {
+        bail!("Failed to slew clock: {}", std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+// Split a number of seconds into whole seconds and microseconds. The microseconds are always
+// positive, so negative times have their seconds rounded down (ex. -1.25s is -2s + 750000us)
+fn timeval(seconds: f64) -> libc::timeval {
+    let micros = (seconds * 1_000_000.0).round() as i64;
+
+    libc::timeval {
+        tv_sec: micros.div_euclid(1_000_000) as libc::time_t,
+        tv_usec: micros.rem_euclid(1_000_000) as libc::suseconds_t,
+    }
+}
+
+// Immediately move the clock by `correction` seconds
+fn step(correction: f64) -> Result<(), Error> {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+
+    unsafe {
+        if libc::clock_gettime(libc::CLOCK_REALTIME, &mut now) != 0 {
+            bail!("Failed to read clock: {}", std::io::Error::last_os_error());
+        }
+    }
+
+    let target = now.tv_sec as f64 + now.tv_nsec as f64 / 1_000_000_000.0 + correction;
+    let new = libc::timespec {
+        tv_sec: target.trunc() as libc::time_t,
+        tv_nsec: (target.fract() * 1_000_000_000.0) as libc::c_long,
+    };
+
+    if unsafe { libc::clock_settime(libc::CLOCK_REALTIME, &new) } != 0 {
+        bail!("Failed to step clock: {}", std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> Config {
+        Config {
+            min_time_status: DEFAULT_MIN_TIME_STATUS,
+            slew_limit: DEFAULT_SLEW_LIMIT,
+            max_jump: DEFAULT_MAX_JUMP,
+            sync_window: Duration::from_secs(DEFAULT_SYNC_WINDOW),
+            latency: DEFAULT_LATENCY,
+            latency_uncertainty: DEFAULT_LATENCY_UNCERTAINTY,
+        }
+    }
+
+    // A plausible GPS time: 2019-04-07 00:00:00 UTC
+    const GPS: f64 = 1_554_595_200.0;
+
+    #[test]
+    fn refuses_untrustworthy_times() {
+        let config = config();
+        let refused = |action: Action| matches!(action, Action::Refuse(_));
+
+        // Time status
+        assert!(refused(decide(&config, false, 140, GPS, 2.0, 0.1)));
+        assert_eq!(decide(&config, false, 160, GPS, 2.0, 0.1), Action::Step);
+
+        // Before the mission
+        assert!(refused(decide(
+            &config,
+            false,
+            180,
+            MIN_PLAUSIBLE - 1.0,
+            2.0,
+            0.1
+        )));
+        assert_eq!(
+            decide(&config, false, 180, MIN_PLAUSIBLE, 2.0, 0.1),
+            Action::Step
+        );
+
+        // Big jumps are only refused once the clock has been synced
+        assert_eq!(decide(&config, false, 180, GPS, -3600.0, 0.1), Action::Step);
+        assert!(refused(decide(&config, true, 180, GPS, -10.001, 0.1)));
+        assert!(refused(decide(&config, true, 180, GPS, 10.001, 0.1)));
+        assert_eq!(decide(&config, true, 180, GPS, 10.0, 0.1), Action::Step);
+        assert_eq!(decide(&config, true, 180, GPS, -10.0, 0.1), Action::Step);
+    }
+
+    #[test]
+    fn slews_small_corrections() {
+        let config = config();
+
+        // Within the uncertainty, there's nothing to do
+        assert_eq!(decide(&config, true, 180, GPS, 0.1, 0.1), Action::Keep);
+        assert_eq!(decide(&config, true, 180, GPS, -0.1, 0.1), Action::Keep);
+        assert_eq!(decide(&config, true, 180, GPS, 0.11, 0.1), Action::Slew);
+
+        assert_eq!(decide(&config, true, 180, GPS, 0.5, 0.1), Action::Slew);
+        assert_eq!(decide(&config, true, 180, GPS, -0.5, 0.1), Action::Slew);
+        assert_eq!(decide(&config, true, 180, GPS, 0.51, 0.1), Action::Step);
+        assert_eq!(decide(&config, true, 180, GPS, -0.51, 0.1), Action::Step);
+
+        // An uncertainty bigger than the slew limit means we never slew
+        assert_eq!(decide(&config, true, 180, GPS, 0.6, 0.8), Action::Keep);
+        assert_eq!(decide(&config, true, 180, GPS, 0.9, 0.8), Action::Step);
+
+        assert_eq!(Action::Keep.name(), "none");
+        assert_eq!(Action::Refuse("".to_owned()).name(), "refused");
+    }
+
+    #[test]
+    fn splits_corrections() {
+        // Integer literals take on whatever sizes `time_t` and `suseconds_t` are
+        macro_rules! check {
+            ($seconds:expr, $sec:expr, $usec:expr) => {
+                let delta = timeval($seconds);
+                assert_eq!((delta.tv_sec, delta.tv_usec), ($sec, $usec), "{}", $seconds);
+            };
+        }
+
+        check!(0.0, 0, 0);
+        check!(0.25, 0, 250_000);
+        check!(1.5, 1, 500_000);
+        check!(-0.25, -1, 750_000);
+        check!(-1.25, -2, 750_000);
+        check!(-2.0, -2, 0);
+        check!(-0.000_000_4, 0, 0);
+        check!(-0.000_001, -1, 999_999);
+    }
+
+    fn log(ms: u64) -> Option<serde_json::Value> {
+        Some(json!({ "time": { "ms": ms, "week": 2048 }, "timeStatus": "FINE" }))
+    }
+
+    #[test]
+    fn watches_for_new_logs() {
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        // Failed polls before the first log don't matter
+        let mut polls = vec![None, log(1000), log(1000), log(61000)].into_iter();
+        let mut read = || Ok(polls.next().unwrap_or_else(|| log(121_000)));
+        let arrival = watch(&mut read, Duration::from_millis(1), deadline).unwrap();
+        assert_eq!(arrival.lock_status["time"]["ms"], 61000);
+        assert!(arrival.after >= arrival.before);
+
+        // If a log arrives while a poll fails, we can't tell when it arrived, so we wait for
+        // the next one
+        let mut polls = vec![log(1000), None, log(61000), log(61000), log(121_000)].into_iter();
+        let mut read = || Ok(polls.next().unwrap_or_else(|| log(181_000)));
+        let arrival = watch(&mut read, Duration::from_millis(1), deadline).unwrap();
+        assert_eq!(arrival.lock_status["time"]["ms"], 121_000);
+
+        // Nothing new before the deadline
+        let mut read = || Ok(log(1000));
+        let deadline = Instant::now() + Duration::from_millis(20);
+        assert!(watch(&mut read, Duration::from_millis(1), deadline).is_err());
+
+        // Errors which aren't tolerated end the watch
+        let mut read = || Err(format_err!("Not responding"));
+        assert!(watch(&mut read, Duration::from_millis(1), deadline).is_err());
+    }
+}
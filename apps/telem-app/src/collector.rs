@@ -0,0 +1,109 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Common interface for all of the telemetry collectors
+//
+// Every subsystem follows the same pattern: get the hardware ready, query its service, tidy up
+// the results, and then store them in the telemetry database. Adding support for new hardware
+// only requires a new `Collector` implementation, which then gets added to the registry in
+// `main.rs`.
+
+use crate::events::EVENT_SUBSYSTEM;
+use crate::point::TelemPoint;
+use crate::schedule::Schedule;
+use crate::staleness;
+use crate::stats;
+use crate::telem_db::send_telem_with;
+use failure::Error;
+use kubos_app::ServiceConfig;
+use log::*;
+use std::time::Instant;
+
+pub trait Collector: Send + Sync {
+    // Name of the collector's section of the config file (ex. "eps" for `[telem-app.eps]`)
+    fn name(&self) -> &str;
+
+    // Subsystem to store the collected telemetry under
+    fn subsystem(&self) -> &str;
+
+    // Schedule to use if the config file doesn't specify one
+    fn default_schedule(&self) -> Schedule;
+
+    // Anything which needs to happen before the subsystem can be queried (ex. powering it on,
+    // or enabling passthrough).
+    // Returns `false` if the subsystem shouldn't be collected right now (ex. it's been commanded
+    // off)
+    fn prepare(&self) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    // Fetch the subsystem's telemetry.
+    // Points are added to `telem_vec` as they're collected, so if one part of a collection
+    // fails, everything gathered before it is still stored
+    fn query(&self, telem_vec: &mut Vec<TelemPoint>) -> Result<(), Error>;
+
+    // Parameters which should change every time the subsystem is collected (ex. its clock).
+    // If they stop advancing, the subsystem is flagged as stale (see `staleness`)
+    fn counters(&self) -> &[&str] {
+        &[]
+    }
+
+    // Adjust the collected points before they're stored
+    fn post_process(&self, telem_vec: Vec<TelemPoint>) -> Vec<TelemPoint> {
+        telem_vec
+    }
+}
+
+// Run a single collection, and record how it went (see `stats`)
+pub fn collect(collector: &dyn Collector) -> Result<(), Error> {
+    let start = Instant::now();
+
+    let mut telem_vec = vec![];
+    let result = match collector.prepare() {
+        Ok(true) => collector.query(&mut telem_vec),
+        Ok(false) => {
+            debug!("Skipping {} telemetry collection", collector.name());
+            return Ok(());
+        }
+        Err(error) => Err(error),
+    };
+    let mut telem_vec = collector.post_process(telem_vec);
+
+    let duration = start.elapsed();
+    let points = telem_vec.len();
+
+    if !telem_vec.is_empty() {
+        // Everything that happens to the points from here on uses the same copy of the config
+        let config = ServiceConfig::new("telem-app");
+
+        let (stale, events) = staleness::check(
+            &config,
+            collector.subsystem(),
+            collector.counters(),
+            &telem_vec,
+        );
+        telem_vec.push(stale);
+
+        send_telem_with(&config, collector.subsystem(), telem_vec);
+        if !events.is_empty() {
+            send_telem_with(&config, EVENT_SUBSYSTEM, events);
+        }
+    }
+
+    stats::record_collection(collector.name(), duration, points, result.is_ok());
+
+    result
+}
@@ -0,0 +1,263 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Derived telemetry parameters
+//
+// Derived parameters are computed onboard from the telemetry we've just collected, and are
+// stored under the `DERIVED` subsystem like any other telemetry. Each one is an arithmetic
+// expression, configured in the `[telem-app.derived]` section of the system's config.toml file:
+//
+// [telem-app.derived]
+// battery_soc = "100 * bm2.remaining_capacity / bm2.full_capacity"
+//
+// Expressions can use numbers, `+`, `-`, `*`, `/` and parentheses, and refer to other telemetry
+// as `<subsystem>.<parameter>`. Whenever a subsystem is collected, every expression which
+// refers to it is re-evaluated using the latest value of each parameter it needs. Expressions
+// which need a parameter we haven't collected yet (ex. right after boot) are skipped.
+
+use crate::point::TelemPoint;
+use failure::{bail, Error};
+use kubos_app::ServiceConfig;
+use log::*;
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::sync::{Mutex, PoisonError};
+
+pub const DERIVED_SUBSYSTEM: &str = "DERIVED";
+
+// Latest (value, timestamp) of every numeric parameter, keyed by (subsystem, parameter)
+static LATEST: Mutex<BTreeMap<(String, String), (f64, f64)>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug)]
+enum Expr {
+    Number(f64),
+    Param(String, String),
+    Negate(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Subtract(Box<Expr>, Box<Expr>),
+    Multiply(Box<Expr>, Box<Expr>),
+    Divide(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn parse(text: &str) -> Result<Expr, Error> {
+        let mut parser = Parser {
+            chars: text.chars().peekable(),
+        };
+        let expr = parser.expression()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(other) => bail!("Unexpected '{}'", other),
+        }
+    }
+
+    // Whether the expression uses any parameters from the given subsystem
+    fn uses(&self, subsystem: &str) -> bool {
+        match self {
+            Expr::Number(_) => false,
+            Expr::Param(sys, _) => sys == subsystem,
+            Expr::Negate(inner) => inner.uses(subsystem),
+            Expr::Add(lhs, rhs)
+            | Expr::Subtract(lhs, rhs)
+            | Expr::Multiply(lhs, rhs)
+            | Expr::Divide(lhs, rhs) => lhs.uses(subsystem) || rhs.uses(subsystem),
+        }
+    }
+
+    // Evaluate the expression, returning the result and the timestamp of the newest parameter it
+    // used. Returns `None` if any of the parameters haven't been collected yet
+    fn eval(&self, latest: &BTreeMap<(String, String), (f64, f64)>) -> Option<(f64, f64)> {
+        let binary = |lhs: &Expr, rhs: &Expr, op: fn(f64, f64) -> f64| {
+            let (lhs, lhs_time) = lhs.eval(latest)?;
+            let (rhs, rhs_time) = rhs.eval(latest)?;
+            Some((op(lhs, rhs), lhs_time.max(rhs_time)))
+        };
+
+        match self {
+            Expr::Number(val) => Some((*val, 0.0)),
+            Expr::Param(sys, param) => latest.get(&(sys.clone(), param.clone())).cloned(),
+            Expr::Negate(inner) => inner.eval(latest).map(|(val, time)| (-val, time)),
+            Expr::Add(lhs, rhs) => binary(lhs, rhs, |lhs, rhs| lhs + rhs),
+            Expr::Subtract(lhs, rhs) => binary(lhs, rhs, |lhs, rhs| lhs - rhs),
+            Expr::Multiply(lhs, rhs) => binary(lhs, rhs, |lhs, rhs| lhs * rhs),
+            Expr::Divide(lhs, rhs) => binary(lhs, rhs, |lhs, rhs| lhs / rhs),
+        }
+    }
+}
+
+// Recursive descent parser for expressions:
+//
+// expression = term { ("+" | "-") term }
+// term       = factor { ("*" | "/") factor }
+// factor     = "-" factor | "(" expression ")" | number | subsystem "." parameter
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    // Look at the next non-whitespace character
+    fn peek(&mut self) -> Option<char> {
+        while let Some(elem) = self.chars.peek() {
+            if !elem.is_whitespace() {
+                return Some(*elem);
+            }
+            self.chars.next();
+        }
+        None
+    }
+
+    fn expression(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.term()?;
+        loop {
+            match self.peek() {
+                Some('+') => {
+                    self.chars.next();
+                    expr = Expr::Add(Box::new(expr), Box::new(self.term()?));
+                }
+                Some('-') => {
+                    self.chars.next();
+                    expr = Expr::Subtract(Box::new(expr), Box::new(self.term()?));
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.factor()?;
+        loop {
+            match self.peek() {
+                Some('*') => {
+                    self.chars.next();
+                    expr = Expr::Multiply(Box::new(expr), Box::new(self.factor()?));
+                }
+                Some('/') => {
+                    self.chars.next();
+                    expr = Expr::Divide(Box::new(expr), Box::new(self.factor()?));
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    fn factor(&mut self) -> Result<Expr, Error> {
+        match self.peek() {
+            Some('-') => {
+                self.chars.next();
+                Ok(Expr::Negate(Box::new(self.factor()?)))
+            }
+            Some('(') => {
+                self.chars.next();
+                let expr = self.expression()?;
+                match self.peek() {
+                    Some(')') => {
+                        self.chars.next();
+                        Ok(expr)
+                    }
+                    _ => bail!("Missing ')'"),
+                }
+            }
+            Some(elem) if elem.is_ascii_digit() || elem == '.' => {
+                let number = self.word(|elem| elem.is_ascii_digit() || elem == '.');
+                match number.parse() {
+                    Ok(val) => Ok(Expr::Number(val)),
+                    Err(_) => bail!("Invalid number '{}'", number),
+                }
+            }
+            Some(elem) if elem.is_ascii_alphabetic() => {
+                let subsystem = self.word(|elem| elem.is_ascii_alphanumeric() || elem == '_');
+                if self.chars.next() != Some('.') {
+                    bail!("Expected '.' after '{}'", subsystem);
+                }
+                let parameter = self.word(|elem| elem.is_ascii_alphanumeric() || elem == '_');
+                if parameter.is_empty() {
+                    bail!("Missing parameter name after '{}.'", subsystem);
+                }
+                Ok(Expr::Param(subsystem, parameter))
+            }
+            Some(other) => bail!("Unexpected '{}'", other),
+            None => bail!("Unexpected end of expression"),
+        }
+    }
+
+    fn word(&mut self, allowed: fn(char) -> bool) -> String {
+        let mut word = String::new();
+        while let Some(elem) = self.chars.peek() {
+            if !allowed(*elem) {
+                break;
+            }
+            word.push(*elem);
+            self.chars.next();
+        }
+        word
+    }
+}
+
+fn load_expressions(config: &ServiceConfig) -> Vec<(String, Expr)> {
+    let section = config.get("derived");
+    let table = match section.as_ref().and_then(|section| section.as_table()) {
+        Some(table) => table,
+        None => return vec![],
+    };
+
+    table
+        .iter()
+        .filter_map(|(name, expression)| {
+            let text = expression.as_str()?;
+            match Expr::parse(text) {
+                Ok(expr) => Some((name.to_owned(), expr)),
+                Err(error) => {
+                    error!(
+                        "Invalid expression for derived parameter {}: {}",
+                        name, error
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+// Record a freshly collected set of points, and compute any derived parameters which depend on
+// them
+pub fn update(config: &ServiceConfig, subsystem: &str, points: &[TelemPoint]) -> Vec<TelemPoint> {
+    let mut latest = LATEST.lock().unwrap_or_else(PoisonError::into_inner);
+
+    for point in points {
+        if let Some(value) = point.value.as_f64() {
+            latest.insert(
+                (subsystem.to_owned(), point.parameter.clone()),
+                (value, point.timestamp),
+            );
+        }
+    }
+
+    load_expressions(config)
+        .into_iter()
+        .filter(|(_, expr)| expr.uses(subsystem))
+        .filter_map(|(name, expr)| match expr.eval(&latest) {
+            Some((value, timestamp)) if value.is_finite() => {
+                Some(TelemPoint::new(name, value, timestamp))
+            }
+            Some(_) => {
+                debug!("Derived parameter {} has no finite value", name);
+                None
+            }
+            None => None,
+        })
+        .collect()
+}
@@ -16,7 +16,10 @@
 
 // Gather telemetry from the NSL Duplex Radio
 
-use crate::telem_db::{process_json, send_telem};
+use crate::collector::Collector;
+use crate::point::TelemPoint;
+use crate::schedule::Schedule;
+use crate::telem_db::{process_json, timestamp_now};
 use failure::Error;
 use kubos_app::*;
 use std::time::Duration;
@@ -43,20 +46,36 @@ const DUPLEX_TELEMETRY: &str = r#"{
     alive
 }"#;
 
-pub fn get_telem() -> Result<(), Error> {
-    let service = ServiceConfig::new("nsl-duplex-d2-comms-service");
+pub struct Duplex;
 
-    let result = query(&service, DUPLEX_TELEMETRY, Some(Duration::from_secs(10)))?;
+impl Collector for Duplex {
+    fn name(&self) -> &str {
+        "duplex"
+    }
+
+    fn subsystem(&self) -> &str {
+        "DUPLEX"
+    }
 
-    let mut telem_vec: Vec<(String, String)> = vec![];
+    fn default_schedule(&self) -> Schedule {
+        Schedule::new(60, 15)
+    }
 
-    // Flatten all of the telemetry
-    if let Some(data) = &result.as_object() {
-        process_json(&mut telem_vec, data, "".to_owned());
+    fn counters(&self) -> &[&str] {
+        &["modemHealth_currentTime"]
     }
 
-    // Send all the telemetry to the telemetry database
-    send_telem("DUPLEX", telem_vec);
+    fn query(&self, telem_vec: &mut Vec<TelemPoint>) -> Result<(), Error> {
+        let service = ServiceConfig::new("nsl-duplex-d2-comms-service");
 
-    Ok(())
+        let timestamp = timestamp_now();
+        let result = query(&service, DUPLEX_TELEMETRY, Some(Duration::from_secs(10)))?;
+
+        // Flatten all of the telemetry
+        if let Some(data) = &result.as_object() {
+            process_json(telem_vec, data, "".to_owned(), timestamp);
+        }
+
+        Ok(())
+    }
 }
@@ -16,20 +16,38 @@
 
 // Gather telemetry from the Clyde Space 3rd gen EPS
 
-use crate::telem_db::{process_json, send_telem};
-use failure::Error;
+use crate::collector::Collector;
+use crate::point::TelemPoint;
+use crate::schedule::Schedule;
+use crate::telem_db::{process_json, timestamp_now};
+use failure::{bail, Error};
 use kubos_app::*;
+use log::*;
+use std::sync::{Mutex, PoisonError};
 use std::time::Duration;
 
-const EPS_TELEMETRY: &str = r#"{
+// The EPS' telemetry is fetched in groups, so that one bad field or slow response only costs us
+// that group, rather than everything. Each group has its own timeout, which can be changed in
+// the config file:
+//
+// [telem-app.eps.timeouts]
+// mb_bcr = 45
+//
+// A group which fails isn't retried during the same collection. It's just fetched first during
+// the next one, so that a slow group can't keep using up the time of the groups after it.
+//
+// The collection isn't reported as hung until all of the groups have had their full timeouts,
+// plus `SCHEDULE_MARGIN`
+
+const EPS_STATUS: &str = r#"{
     telemetry {
-        lastEpsError { 
+        lastEpsError {
             daughterboard,
-            motherboard,    
+            motherboard,
         },
         boardStatus {
-            daughterboard, 
-            motherboard, 
+            daughterboard,
+            motherboard,
         },
         reset {
             automaticSoftware {
@@ -48,7 +66,12 @@ const EPS_TELEMETRY: &str = r#"{
                 daughterboard,
                 motherboard
             }
-        },
+        }
+    }
+}"#;
+
+const EPS_MB_BCR: &str = r#"{
+    telemetry {
         motherboard {
             VoltageFeedingBcr1,
             CurrentBcr1Sa1a,
@@ -71,6 +94,13 @@ const EPS_TELEMETRY: &str = r#"{
             ArrayTempSa3b,
             SunDetectorSa3a,
             SunDetectorSa3b,
+        }
+    }
+}"#;
+
+const EPS_MB_OUTPUT: &str = r#"{
+    telemetry {
+        motherboard {
             BcrOutputCurrent,
             BcrOutputVoltage,
             CurrentDraw3V3,
@@ -104,7 +134,12 @@ const EPS_TELEMETRY: &str = r#"{
             OutputVoltageSwitch10,
             OutputCurrentSwitch10,
             BoardTemperature,
-        },
+        }
+    }
+}"#;
+
+const EPS_DB_BCR: &str = r#"{
+    telemetry {
         daughterboard {
             VoltageFeedingBcr4,
             CurrentBcr4Sa4a,
@@ -153,93 +188,287 @@ const EPS_TELEMETRY: &str = r#"{
     }
 }"#;
 
-pub fn get_telem() -> Result<(), Error> {
-    let service = ServiceConfig::new("clyde-3g-eps-service");
+struct Group {
+    name: &'static str,
+    query: &'static str,
+    // Default timeout, in seconds. Each field can take a couple of seconds to fetch
+    timeout: u64,
+    process: fn(&serde_json::Value, &mut Vec<TelemPoint>, f64),
+}
+
+const GROUPS: [Group; 4] = [
+    Group {
+        name: "status",
+        query: EPS_STATUS,
+        timeout: 30,
+        process: process_status,
+    },
+    Group {
+        name: "mb_bcr",
+        query: EPS_MB_BCR,
+        timeout: 45,
+        process: process_mb,
+    },
+    Group {
+        name: "mb_output",
+        query: EPS_MB_OUTPUT,
+        timeout: 65,
+        process: process_mb,
+    },
+    Group {
+        name: "db_bcr",
+        query: EPS_DB_BCR,
+        timeout: 90,
+        process: process_db,
+    },
+];
+
+// Extra time, on top of the group timeouts, before a collection is reported as hung
+const SCHEDULE_MARGIN: u64 = 60;
+
+// Groups which failed during the last collection. These are fetched first next time
+static FAILED: Mutex<Vec<&'static str>> = Mutex::new(vec![]);
+
+pub struct Eps;
+
+impl Collector for Eps {
+    fn name(&self) -> &str {
+        "eps"
+    }
+
+    fn subsystem(&self) -> &str {
+        "EPS"
+    }
+
+    // It can take ~200 seconds to fetch all of the EPS' telemetry
+    fn default_schedule(&self) -> Schedule {
+        let timeouts = timeouts();
+        let total: u64 = GROUPS
+            .iter()
+            .map(|group| group_timeout(&timeouts, group).as_secs())
+            .sum();
+
+        Schedule::new(300, total + SCHEDULE_MARGIN)
+    }
+
+    fn query(&self, telem_vec: &mut Vec<TelemPoint>) -> Result<(), Error> {
+        let service = ServiceConfig::new("clyde-3g-eps-service");
+        let timeouts = timeouts();
 
-    // Get all the basic telemetry
-    // It takes 60ms to fetch a single field
-    let result = query(&service, EPS_TELEMETRY, Some(Duration::from_secs(200)))?;
+        let mut failed = FAILED.lock().unwrap_or_else(PoisonError::into_inner);
 
-    println!("EPS Result: {:?}", result);
+        *failed = fetch_groups(order(&failed), telem_vec, |group| {
+            query(&service, group.query, Some(group_timeout(&timeouts, group)))
+        });
 
-    let telemetry = &result["telemetry"];
+        if !failed.is_empty() {
+            bail!(
+                "Failed to fetch EPS telemetry groups: {}",
+                failed.join(", ")
+            );
+        }
+
+        Ok(())
+    }
+}
+
+// The `[telem-app.eps.timeouts]` section of the config file
+fn timeouts() -> Option<toml::Value> {
+    ServiceConfig::new("telem-app")
+        .get("eps")
+        .and_then(|section| section.get("timeouts").cloned())
+}
+
+fn group_timeout(timeouts: &Option<toml::Value>, group: &Group) -> Duration {
+    let secs = timeouts
+        .as_ref()
+        .and_then(|timeouts| timeouts.get(group.name))
+        .and_then(|val| val.as_integer())
+        .map(|val| val as u64)
+        .unwrap_or(group.timeout);
+
+    Duration::from_secs(secs)
+}
 
-    let mut telem_vec: Vec<(String, String)> = vec![];
+// The order to fetch the groups in: any which failed last time first, then the rest
+fn order(failed: &[&str]) -> Vec<&'static Group> {
+    let mut groups: Vec<&Group> = GROUPS.iter().collect();
+    groups.sort_by_key(|group| !failed.contains(&group.name));
+    groups
+}
+
+// Fetch each group in turn, adding the points from each one which succeeds.
+// Returns the names of the groups which failed
+fn fetch_groups<F>(
+    groups: Vec<&'static Group>,
+    telem_vec: &mut Vec<TelemPoint>,
+    mut fetch: F,
+) -> Vec<&'static str>
+where
+    F: FnMut(&Group) -> Result<serde_json::Value, Error>,
+{
+    let mut failed = vec![];
 
+    for group in groups {
+        let timestamp = timestamp_now();
+        match fetch(group) {
+            Ok(result) => (group.process)(&result["telemetry"], telem_vec, timestamp),
+            Err(error) => {
+                warn!("Failed to fetch EPS {} telemetry: {:?}", group.name, error);
+                failed.push(group.name);
+            }
+        }
+    }
+
+    failed
+}
+
+fn process_status(telemetry: &serde_json::Value, telem_vec: &mut Vec<TelemPoint>, timestamp: f64) {
     let last_error = &telemetry["lastEpsError"];
 
     if let Some(data) = last_error["motherboard"].as_str() {
-        telem_vec.push(("last_error_mb".to_owned(), data.to_owned()));
+        telem_vec.push(TelemPoint::new("last_error_mb", data, timestamp));
     }
 
     if let Some(data) = last_error["daughterboard"].as_str() {
-        telem_vec.push(("last_error_mb".to_owned(), data.to_owned()));
+        telem_vec.push(TelemPoint::new("last_error_db", data, timestamp));
     }
 
     let board_status = &telemetry["boardStatus"];
 
     if let Some(data) = board_status["motherboard"].as_array() {
         let composite: Vec<&str> = data.iter().filter_map(|elem| elem.as_str()).collect();
-        telem_vec.push(("board_status_mb".to_owned(), composite.join("|")));
+        telem_vec.push(TelemPoint::new(
+            "board_status_mb",
+            composite.join("|"),
+            timestamp,
+        ));
     }
 
     if let Some(data) = board_status["daughterboard"].as_array() {
         let composite: Vec<&str> = data.iter().filter_map(|elem| elem.as_str()).collect();
-        telem_vec.push(("board_status_db".to_owned(), composite.join("|")));
+        telem_vec.push(TelemPoint::new(
+            "board_status_db",
+            composite.join("|"),
+            timestamp,
+        ));
     }
 
     let reset = &telemetry["reset"];
 
-    if let Some(data) = reset["automaticSoftware"]["motherboard"].as_u64() {
-        telem_vec.push(("reset_sw_mb".to_owned(), format!("{}", data).to_owned()));
-    }
+    let resets = [
+        ("automaticSoftware", "sw"),
+        ("brownOut", "brownout"),
+        ("manual", "manual"),
+        ("watchdog", "wd"),
+    ];
 
-    if let Some(data) = reset["automaticSoftware"]["daughterboard"].as_u64() {
-        telem_vec.push(("reset_sw_db".to_owned(), format!("{}", data).to_owned()));
-    }
+    for (kind, name) in resets.iter() {
+        if let Some(data) = reset[kind]["motherboard"].as_u64() {
+            telem_vec.push(TelemPoint::new(
+                format!("reset_{}_mb", name),
+                data,
+                timestamp,
+            ));
+        }
 
-    if let Some(data) = reset["brownOut"]["motherboard"].as_u64() {
-        telem_vec.push((
-            "reset_brownout_mb".to_owned(),
-            format!("{}", data).to_owned(),
-        ));
+        if let Some(data) = reset[kind]["daughterboard"].as_u64() {
+            telem_vec.push(TelemPoint::new(
+                format!("reset_{}_db", name),
+                data,
+                timestamp,
+            ));
+        }
     }
+}
 
-    if let Some(data) = reset["brownOut"]["daughterboard"].as_u64() {
-        telem_vec.push((
-            "reset_brownout_db".to_owned(),
-            format!("{}", data).to_owned(),
-        ));
+fn process_mb(telemetry: &serde_json::Value, telem_vec: &mut Vec<TelemPoint>, timestamp: f64) {
+    if let Some(data) = telemetry["motherboard"].as_object() {
+        process_json(telem_vec, data, "mb_".to_owned(), timestamp);
     }
+}
 
-    if let Some(data) = reset["manual"]["motherboard"].as_u64() {
-        telem_vec.push(("reset_manual_mb".to_owned(), format!("{}", data).to_owned()));
+fn process_db(telemetry: &serde_json::Value, telem_vec: &mut Vec<TelemPoint>, timestamp: f64) {
+    if let Some(data) = telemetry["daughterboard"].as_object() {
+        process_json(telem_vec, data, "db_".to_owned(), timestamp);
     }
+}
 
-    if let Some(data) = reset["manual"]["daughterboard"].as_u64() {
-        telem_vec.push(("reset_manual_db".to_owned(), format!("{}", data).to_owned()));
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use failure::format_err;
+    use serde_json::json;
 
-    if let Some(data) = reset["watchdog"]["motherboard"].as_u64() {
-        telem_vec.push(("reset_wd_mb".to_owned(), format!("{}", data).to_owned()));
+    fn names(groups: &[&Group]) -> Vec<&'static str> {
+        groups.iter().map(|group| group.name).collect()
     }
 
-    if let Some(data) = reset["watchdog"]["daughterboard"].as_u64() {
-        telem_vec.push(("reset_wd_db".to_owned(), format!("{}", data).to_owned()));
+    #[test]
+    fn failed_groups_go_first() {
+        assert_eq!(
+            names(&order(&[])),
+            ["status", "mb_bcr", "mb_output", "db_bcr"]
+        );
+        // Otherwise, the groups keep their usual order
+        assert_eq!(
+            names(&order(&["db_bcr", "mb_bcr"])),
+            ["mb_bcr", "db_bcr", "status", "mb_output"]
+        );
+        assert_eq!(
+            names(&order(&["unknown"])),
+            ["status", "mb_bcr", "mb_output", "db_bcr"]
+        );
     }
 
-    let mb_telem = &telemetry["motherboard"].as_object();
-    if let Some(data) = mb_telem {
-        process_json(&mut telem_vec, data, "mb_".to_owned());
-    }
+    #[test]
+    fn failed_groups_dont_cost_the_others() {
+        let mut telem_vec = vec![];
+        let mut fetched = vec![];
 
-    let db_telem = &telemetry["daughterboard"].as_object();
-    if let Some(data) = db_telem {
-        process_json(&mut telem_vec, data, "db_".to_owned());
-    }
+        let failed = fetch_groups(order(&["mb_output"]), &mut telem_vec, |group| {
+            fetched.push(group.name);
+            match group.name {
+                "mb_output" => Err(format_err!("Timed out")),
+                "status" => Ok(json!({
+                    "telemetry": { "lastEpsError": { "motherboard": "None" } }
+                })),
+                "mb_bcr" => Ok(json!({
+                    "telemetry": { "motherboard": { "VoltageFeedingBcr1": 4.5 } }
+                })),
+                _ => Ok(json!({
+                    "telemetry": { "daughterboard": { "VoltageFeedingBcr9": 0.5 } }
+                })),
+            }
+        });
 
-    // Send all the telemetry to the telemetry database
-    send_telem("EPS", telem_vec);
+        // Every group was tried, and only the one which failed is reported
+        assert_eq!(fetched, ["mb_output", "status", "mb_bcr", "db_bcr"]);
+        assert_eq!(failed, ["mb_output"]);
 
-    Ok(())
+        let parameters: Vec<&str> = telem_vec
+            .iter()
+            .map(|point| point.parameter.as_str())
+            .collect();
+        assert_eq!(
+            parameters,
+            [
+                "last_error_mb",
+                "mb_VoltageFeedingBcr1",
+                "db_VoltageFeedingBcr9"
+            ]
+        );
+
+        // Next time, the failed group is fetched first
+        assert_eq!(names(&order(&failed))[0], "mb_output");
+    }
+
+    #[test]
+    fn schedule_outlasts_the_groups() {
+        let total: u64 = GROUPS.iter().map(|group| group.timeout).sum();
+        assert_eq!(
+            Eps.default_schedule().timeout,
+            Duration::from_secs(total + SCHEDULE_MARGIN)
+        );
+    }
 }
@@ -0,0 +1,34 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Discrete events
+//
+// Events are things which happened at a particular moment (ex. a parameter going out of limits),
+// rather than periodic measurements. They're stored under the `EVENT` subsystem, with the
+// parameter naming the kind of event and the value describing what happened, and are also
+// written to the warning log so that they show up without having to query the database.
+
+use crate::point::{TelemPoint, TelemValue};
+use log::*;
+
+pub const EVENT_SUBSYSTEM: &str = "EVENT";
+
+// Create a new event, to be sent under `EVENT_SUBSYSTEM`
+pub fn event(kind: &str, description: String, timestamp: f64) -> TelemPoint {
+    warn!("{}: {}", kind, description);
+
+    TelemPoint::new(kind, TelemValue::Str(description), timestamp)
+}
@@ -0,0 +1,227 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Limit checking for collected telemetry
+//
+// Limits are configured in the `[telem-app]` section of the system's config.toml file, with one
+// entry per parameter:
+//
+// [[telem-app.limits]]
+// subsystem = "EPS"
+// parameter = "mb_BoardTemperature"
+// red_low = -20.0
+// yellow_low = -10.0
+// yellow_high = 50.0
+// red_high = 70.0
+// persistence = 3 # Consecutive samples needed before the limit state changes
+//
+// Any of the four limits may be left out. A parameter has to be outside (or back inside) its
+// limits for `persistence` samples in a row before we report a violation (or a recovery), so a
+// single noisy reading doesn't generate a pair of events.
+
+use crate::events::event;
+use crate::point::TelemPoint;
+use kubos_app::ServiceConfig;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Mutex, PoisonError};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Level {
+    Nominal,
+    YellowLow,
+    YellowHigh,
+    RedLow,
+    RedHigh,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Level::Nominal => "NOMINAL",
+            Level::YellowLow => "YELLOW_LOW",
+            Level::YellowHigh => "YELLOW_HIGH",
+            Level::RedLow => "RED_LOW",
+            Level::RedHigh => "RED_HIGH",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug)]
+struct Limit {
+    subsystem: String,
+    parameter: String,
+    red_low: Option<f64>,
+    yellow_low: Option<f64>,
+    yellow_high: Option<f64>,
+    red_high: Option<f64>,
+    persistence: u32,
+}
+
+impl Limit {
+    fn from_config(entry: &toml::Value) -> Option<Limit> {
+        let limit = |key| {
+            entry.get(key).and_then(|val| {
+                val.as_float()
+                    .or_else(|| val.as_integer().map(|val| val as f64))
+            })
+        };
+
+        Some(Limit {
+            subsystem: entry.get("subsystem")?.as_str()?.to_owned(),
+            parameter: entry.get("parameter")?.as_str()?.to_owned(),
+            red_low: limit("red_low"),
+            yellow_low: limit("yellow_low"),
+            yellow_high: limit("yellow_high"),
+            red_high: limit("red_high"),
+            persistence: entry
+                .get("persistence")
+                .and_then(|val| val.as_integer())
+                .map(|val| val.max(1) as u32)
+                .unwrap_or(1),
+        })
+    }
+
+    fn level(&self, value: f64) -> Level {
+        let below = |limit: Option<f64>| limit.map(|limit| value < limit).unwrap_or(false);
+        let above = |limit: Option<f64>| limit.map(|limit| value > limit).unwrap_or(false);
+
+        if below(self.red_low) {
+            Level::RedLow
+        } else if above(self.red_high) {
+            Level::RedHigh
+        } else if below(self.yellow_low) {
+            Level::YellowLow
+        } else if above(self.yellow_high) {
+            Level::YellowHigh
+        } else {
+            Level::Nominal
+        }
+    }
+
+    // The limit which was crossed to reach the given level
+    fn threshold(&self, level: Level) -> Option<f64> {
+        match level {
+            Level::Nominal => None,
+            Level::YellowLow => self.yellow_low,
+            Level::YellowHigh => self.yellow_high,
+            Level::RedLow => self.red_low,
+            Level::RedHigh => self.red_high,
+        }
+    }
+}
+
+// Where each limited parameter currently stands
+struct State {
+    // The level we last reported
+    level: Level,
+    // The level the most recent samples have been at, and how many samples in a row that's been
+    candidate: Level,
+    count: u32,
+}
+
+// Keyed by (subsystem, parameter)
+static STATES: Mutex<BTreeMap<(String, String), State>> = Mutex::new(BTreeMap::new());
+
+fn load_limits(config: &ServiceConfig) -> Vec<Limit> {
+    config
+        .get("limits")
+        .and_then(|limits| limits.as_array().cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Limit::from_config)
+        .collect()
+}
+
+// Check a freshly collected set of points against their limits.
+// Returns an event for each parameter which has gone out of limits, changed severity, or
+// recovered
+pub fn check(config: &ServiceConfig, subsystem: &str, points: &[TelemPoint]) -> Vec<TelemPoint> {
+    let limits: Vec<Limit> = load_limits(config)
+        .into_iter()
+        .filter(|limit| limit.subsystem == subsystem)
+        .collect();
+
+    if limits.is_empty() {
+        return vec![];
+    }
+
+    let mut states = STATES.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut events = vec![];
+
+    for point in points {
+        let limit = match limits
+            .iter()
+            .find(|limit| limit.parameter == point.parameter)
+        {
+            Some(limit) => limit,
+            None => continue,
+        };
+        let value = match point.value.as_f64() {
+            Some(value) => value,
+            None => continue,
+        };
+
+        let level = limit.level(value);
+        let state = states
+            .entry((subsystem.to_owned(), point.parameter.clone()))
+            .or_insert(State {
+                level: Level::Nominal,
+                candidate: Level::Nominal,
+                count: 0,
+            });
+
+        if level == state.candidate {
+            state.count = state.count.saturating_add(1);
+        } else {
+            state.candidate = level;
+            state.count = 1;
+        }
+
+        if state.candidate == state.level || state.count < limit.persistence {
+            continue;
+        }
+
+        let previous = state.level;
+        state.level = level;
+
+        let unit = point
+            .unit
+            .map(|unit| format!(" {}", unit))
+            .unwrap_or_default();
+        let description = match limit.threshold(level) {
+            Some(threshold) => format!(
+                "{}.{} {} -> {}: {}{} (limit {}{})",
+                subsystem, point.parameter, previous, level, value, unit, threshold, unit
+            ),
+            None => format!(
+                "{}.{} {} -> {}: {}{}",
+                subsystem, point.parameter, previous, level, value, unit
+            ),
+        };
+
+        let kind = if level == Level::Nominal {
+            "limit_recovery"
+        } else {
+            "limit_violation"
+        };
+
+        events.push(event(kind, description, point.timestamp));
+    }
+
+    events
+}
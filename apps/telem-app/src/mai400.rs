@@ -16,9 +16,13 @@
 
 // Gather telemetry from the Maryland Aerospace MAI-400
 
-use crate::telem_db::{process_json, send_telem};
+use crate::collector::Collector;
+use crate::point::TelemPoint;
+use crate::schedule::Schedule;
+use crate::telem_db::{process_json, timestamp_now};
 use failure::{bail, Error};
 use kubos_app::*;
+use power_state::Unit;
 use std::time::Duration;
 
 const MAI_NOMINAL: &str = r#"{
@@ -183,94 +187,175 @@ const MAI_ROTATING: &str = r#"{
     }
 }"#;
 
-const MAI_POWER: &str = r#"
-    mutation {
-        passthrough(module: "aim2", command: "AIM:ADCS:POW ON") {
-            status,
-            command
-        }
+// Conversions from the MAI-400's raw values to engineering units.
+// Each entry is (parameter, scale, offset, unit), where the converted value is
+// `raw * scale + offset`. Entries also apply to the individual elements of array parameters
+// (ex. an entry for "omegaB" would cover "omegaB_0" through "omegaB_2").
+//
+// The converted values are stored alongside the raw ones, as "<parameter>_eng". Only values
+// which need converting are listed, so nothing is stored twice
+const CALIBRATION: &[(&str, f64, f64, &str)] = &[("rwsMotorTemp", 0.040_293_0, -50.0, "degC")];
+
+// Quaternions are reported as scaled integers (1 = 0.0001). These are scaled and then
+// normalized together, so that rounding in the raw values doesn't leave us with something that
+// isn't a valid rotation
+const QUATERNIONS: &[&str] = &["qboCmd", "qboHat"];
+const QUATERNION_SCALE: f64 = 0.0001;
+
+// Times reported in GPS seconds. Each one also gets stored as a Unix time, named `<field>_utc`
+const GPS_TIMES: &[&str] = &["gpsTime", "orbitEpoch", "orbitEpochNext"];
+
+// Check whether a parameter is the given field, or one of its array elements
+fn is_field(parameter: &str, field: &str) -> bool {
+    parameter == field
+        || (parameter.starts_with(field) && parameter[field.len()..].starts_with('_'))
+}
+
+pub struct Mai400;
+
+impl Collector for Mai400 {
+    fn name(&self) -> &str {
+        "mai400"
     }
-"#;
 
-const MAI_COMM: &str = r#"
-    mutation {
-        passthrough(module: "aim2", command: "AIM:ADCS:COMM UART0") {
-            status,
-            command
-        }
+    fn subsystem(&self) -> &str {
+        "MAI400"
     }
-"#;
 
-const MAI_PASS: &str = r#"
-    mutation {
-        passthrough(module: "aim2", command: "AIM:ADCS:PASS ON") {
-            status,
-            command
-        }
+    fn default_schedule(&self) -> Schedule {
+        Schedule::new(60, 15)
     }
-"#;
 
-pub fn get_telem() -> Result<(), Error> {
-    // Make sure the MAI-400 is on and able to communicate with us
-    let service = ServiceConfig::new("pumpkin-mcu-service");
+    fn counters(&self) -> &[&str] {
+        &["gpsTime"]
+    }
 
-    let _ = query(&service, MAI_POWER, Some(Duration::from_millis(500)))?;
+    // Make sure the MAI-400 is on and able to communicate with us, unless the ground has
+    // commanded it off
+    fn prepare(&self) -> Result<bool, Error> {
+        power_state::ensure(Unit::Adcs)
+    }
 
-    let _ = query(&service, MAI_COMM, Some(Duration::from_millis(500)))?;
+    // Add the engineering-unit version of each of the raw values we know how to convert
+    fn post_process(&self, mut telem_vec: Vec<TelemPoint>) -> Vec<TelemPoint> {
+        let mut converted = vec![];
 
-    let _ = query(&service, MAI_PASS, Some(Duration::from_millis(500)))?;
+        for point in telem_vec.iter() {
+            let raw = match point.value.as_f64() {
+                Some(raw) => raw,
+                None => continue,
+            };
 
-    let service = ServiceConfig::new("mai400-service");
+            if let Some((_, scale, offset, unit)) = CALIBRATION
+                .iter()
+                .find(|(field, _, _, _)| is_field(&point.parameter, field))
+            {
+                let mut eng = TelemPoint::new(
+                    format!("{}_eng", point.parameter),
+                    raw * scale + offset,
+                    point.timestamp,
+                );
+                eng.unit = Some(unit);
+                converted.push(eng);
+            }
+        }
 
-    // The MAI-400 has a *bunch* of fields, so we're going to break this into three chunks to
-    // help reduce the amount of data returned at one time
+        for field in QUATERNIONS {
+            let elements: Vec<&TelemPoint> = (0..4)
+                .filter_map(|index| {
+                    let name = format!("{}_{}", field, index);
+                    telem_vec.iter().find(|point| point.parameter == name)
+                })
+                .collect();
+            let values: Vec<f64> = elements
+                .iter()
+                .filter_map(|point| point.value.as_f64())
+                .map(|raw| raw * QUATERNION_SCALE)
+                .collect();
 
-    // Get the nominal telemetry
+            if values.len() != 4 {
+                continue;
+            }
 
-    let result = query(&service, MAI_NOMINAL, Some(Duration::from_secs(2)))?;
+            let norm = values.iter().map(|val| val * val).sum::<f64>().sqrt();
+            if norm == 0.0 {
+                continue;
+            }
 
-    if result["telemetry"]["nominal"]["gpsTime"] == 0 {
-        bail!("MAI-400 offline");
-    }
+            for (point, value) in elements.iter().zip(values.iter()) {
+                converted.push(TelemPoint::new(
+                    format!("{}_eng", point.parameter),
+                    value / norm,
+                    point.timestamp,
+                ));
+            }
+        }
 
-    let nominal = &result["telemetry"]["nominal"].as_object();
+        for field in GPS_TIMES {
+            let time = telem_vec
+                .iter()
+                .find(|point| point.parameter == *field)
+                .and_then(|point| point.value.as_f64().map(|raw| (raw, point.timestamp)));
 
-    let mut telem_vec: Vec<(String, String)> = vec![];
+            // Zero means the time isn't set
+            if let Some((raw, timestamp)) = time.filter(|(raw, _)| *raw > 0.0) {
+                converted.push(TelemPoint::new(
+                    format!("{}_utc", field),
+                    gps_time::gps_to_unix(raw),
+                    timestamp,
+                ));
+            }
+        }
 
-    // Auto-convert returned JSON into a flat key-value vector
-    if let Some(data) = nominal {
-        process_json(&mut telem_vec, data, "".to_owned());
-        // Send it to the telemetry database
-        send_telem("MAI400", telem_vec);
+        telem_vec.extend(converted);
+        telem_vec
     }
 
-    // Get the debug telemetry, minus the rotating variables
-    let result = query(&service, MAI_DEBUG, Some(Duration::from_secs(2)))?;
+    fn query(&self, telem_vec: &mut Vec<TelemPoint>) -> Result<(), Error> {
+        let service = ServiceConfig::new("mai400-service");
 
-    let debug = &result["telemetry"]["debug"].as_object();
+        // The MAI-400 has a *bunch* of fields, so we're going to break this into three chunks to
+        // help reduce the amount of data returned at one time
 
-    let mut telem_vec: Vec<(String, String)> = vec![];
+        // Get the nominal telemetry
 
-    // Auto-convert returned JSON into a flat key-value vector
-    if let Some(data) = debug {
-        process_json(&mut telem_vec, data, "".to_owned());
-        // Send it to the telemetry database
-        send_telem("MAI400", telem_vec);
-    }
+        let timestamp = timestamp_now();
+        let result = query(&service, MAI_NOMINAL, Some(Duration::from_secs(2)))?;
 
-    // Get the rotating variables telemetry
-    let result = query(&service, MAI_ROTATING, Some(Duration::from_secs(2)))?;
+        if result["telemetry"]["nominal"]["gpsTime"] == 0 {
+            power_state::mark_off(Unit::Adcs);
+            bail!("MAI-400 offline");
+        }
 
-    let rotating = &result["telemetry"]["debug"]["rotating"].as_object();
+        let nominal = &result["telemetry"]["nominal"].as_object();
 
-    let mut telem_vec: Vec<(String, String)> = vec![];
+        // Auto-convert returned JSON into a flat key-value vector
+        if let Some(data) = nominal {
+            process_json(telem_vec, data, "".to_owned(), timestamp);
+        }
 
-    // Auto-convert returned JSON into a flat key-value vector
-    if let Some(data) = rotating {
-        process_json(&mut telem_vec, data, "".to_owned());
-        // Send it to the telemetry database
-        send_telem("MAI400", telem_vec);
-    }
+        // Get the debug telemetry, minus the rotating variables
+        let timestamp = timestamp_now();
+        let result = query(&service, MAI_DEBUG, Some(Duration::from_secs(2)))?;
 
-    Ok(())
+        let debug = &result["telemetry"]["debug"].as_object();
+
+        // Auto-convert returned JSON into a flat key-value vector
+        if let Some(data) = debug {
+            process_json(telem_vec, data, "".to_owned(), timestamp);
+        }
+
+        // Get the rotating variables telemetry
+        let timestamp = timestamp_now();
+        let result = query(&service, MAI_ROTATING, Some(Duration::from_secs(2)))?;
+
+        let rotating = &result["telemetry"]["debug"]["rotating"].as_object();
+
+        // Auto-convert returned JSON into a flat key-value vector
+        if let Some(data) = rotating {
+            process_json(telem_vec, data, "".to_owned(), timestamp);
+        }
+
+        Ok(())
+    }
 }
@@ -14,81 +14,120 @@
 // limitations under the License.
 //
 
+mod burst;
+mod clock_sync;
+mod collector;
+mod derived;
 mod duplex;
 mod eps;
+mod events;
+mod limits;
 mod mai400;
 mod obc;
 mod oem6;
+mod orbit;
+mod point;
+mod schedule;
+mod spool;
+mod staleness;
+mod stats;
 mod sup_mcu;
 mod telem_db;
+mod units;
 
-use failure::Error;
+use crate::collector::Collector;
+use crate::sup_mcu::SupMcu;
+use failure::{bail, Error};
 use kubos_app::*;
 use log::*;
-use std::thread;
-use std::time::Duration;
+use power_state::{PowerState, Unit};
+use std::sync::Arc;
+
+// All of the subsystems we collect telemetry from
+fn registry() -> Vec<Arc<dyn Collector>> {
+    let mut collectors: Vec<Arc<dyn Collector>> = vec![
+        Arc::new(duplex::Duplex),
+        Arc::new(obc::Obc),
+        Arc::new(eps::Eps),
+        Arc::new(mai400::Mai400),
+        Arc::new(oem6::Oem6),
+        Arc::new(clock_sync::ClockSync),
+        Arc::new(orbit::Orbit),
+    ];
+
+    // One collector for each of the Sup MCU modules present in the system
+    for module in sup_mcu::modules() {
+        collectors.push(Arc::new(SupMcu { module }));
+    }
+
+    collectors
+}
 
 struct MyApp;
 
 impl AppHandler for MyApp {
     fn on_boot(&self, _args: Vec<String>) -> Result<(), Error> {
-        loop {
-            if let Err(error) = duplex::get_telem() {
-                error!("Error while fetching Duplex telemetry: {:?}", error);
-            }
-
-            if let Err(error) = obc::get_telem() {
-                error!("Error while fetching OBC telemetry: {:?}", error);
-            }
-
-            if let Err(error) = eps::get_telem() {
-                error!("Error while fetching EPS telemetry: {:?}", error);
-            }
-
-            if let Err(error) = mai400::get_telem() {
-                error!("Error while fetching MAI-400 telemetry: {:?}", error);
-            }
+        let handles: Vec<_> = registry().into_iter().filter_map(schedule::spawn).collect();
 
-            if let Err(error) = oem6::get_telem() {
-                error!("Error while fetching OEM6 telemetry: {:?}", error);
+        // The collector threads should run forever, so we only get past here if something has
+        // gone terribly wrong
+        for handle in handles {
+            if let Err(error) = handle.join() {
+                error!("Collector thread panicked: {:?}", error);
             }
-
-            if let Err(error) = sup_mcu::get_telem() {
-                error!("Error while fetching Sup MCU telemetry: {:?}", error);
-            }
-
-            thread::sleep(Duration::from_secs(60));
-        }
-    }
-
-    fn on_command(&self, _args: Vec<String>) -> Result<(), Error> {
-        if let Err(error) = duplex::get_telem() {
-            error!("Error while fetching Duplex telemetry: {:?}", error);
-        }
-
-        if let Err(error) = obc::get_telem() {
-            error!("Error while fetching OBC telemetry: {:?}", error);
         }
 
-        if let Err(error) = eps::get_telem() {
-            error!("Error while fetching EPS telemetry: {:?}", error);
-        }
+        Ok(())
+    }
 
-        if let Err(error) = mai400::get_telem() {
-            error!("Error while fetching MAI-400 telemetry: {:?}", error);
+    // Run some or all of the collectors on demand, either once or as a burst. See `burst` for
+    // the available arguments
+    fn on_command(&self, args: Vec<String>) -> Result<(), Error> {
+        if args.first().map(|arg| arg.as_str()) == Some("power") {
+            return power_command(&args[1..]);
         }
 
-        if let Err(error) = oem6::get_telem() {
-            error!("Error while fetching OEM6 telemetry: {:?}", error);
-        }
+        let request = burst::Request::parse(&args)?;
+        let collectors = request.select(registry())?;
 
-        if let Err(error) = sup_mcu::get_telem() {
-            error!("Error while fetching Sup MCU telemetry: {:?}", error);
+        match request.burst {
+            Some(burst) => burst::run(collectors, burst),
+            None => {
+                burst::run_once(collectors);
+                Ok(())
+            }
         }
-        Ok(())
     }
 }
 
+// Change the commanded power state of a unit:
+//
+// telem-app power <unit> <on|off>
+//
+// The new state is saved, so it's kept across reboots, and collectors stop (or start) touching
+// the unit right away. The radios aren't commandable, so they're rejected as unknown units
+fn power_command(args: &[String]) -> Result<(), Error> {
+    let unit = match args.first().and_then(|name| Unit::from_name(name)) {
+        Some(unit) => unit,
+        None => bail!(
+            "Unknown unit. Expected one of: {}",
+            Unit::ALL
+                .iter()
+                .map(|unit| unit.name())
+                .collect::<Vec<&str>>()
+                .join(", ")
+        ),
+    };
+
+    let state = match args.get(1).map(|state| state.as_str()) {
+        Some("on") => PowerState::On,
+        Some("off") => PowerState::Off,
+        _ => bail!("Expected a power state of 'on' or 'off'"),
+    };
+
+    power_state::command(unit, state)
+}
+
 fn main() -> Result<(), Error> {
     let app = MyApp;
     app_main!(&app, log::LevelFilter::Info)?;
@@ -15,10 +15,20 @@
 //
 
 // Gather telemetry from the MBM2 itself
+//
+// Memory usage comes from the monitor service. Everything else is read directly from /proc and
+// /sys, since the monitor service doesn't expose it.
 
-use crate::telem_db::{process_json, send_telem};
-use failure::Error;
+use crate::collector::Collector;
+use crate::point::TelemPoint;
+use crate::schedule::Schedule;
+use crate::telem_db::{process_json, timestamp_now};
+use failure::{bail, format_err, Error};
 use kubos_app::*;
+use log::*;
+use std::fs;
+use std::process::Command;
+use std::sync::{Mutex, PoisonError};
 use std::time::Duration;
 
 const OBC_TELEMETRY: &str = r#"{
@@ -28,21 +38,298 @@ const OBC_TELEMETRY: &str = r#"{
     }
 }"#;
 
-pub fn get_telem() -> Result<(), Error> {
+// Partition number of each of the eMMC's partitions we care about.
+// The eMMC is mmcblk1 if the SD card is present, otherwise it's mmcblk0
+const PARTITIONS: &[(&str, u8)] = &[("rootfs", 2), ("upgrade", 3), ("home", 4)];
+
+const SOC_TEMP: &str = "/sys/class/thermal/thermal_zone0/temp";
+
+// Number of processes to report in the list of top memory consumers
+const DEFAULT_TOP_PROCESSES: usize = 3;
+
+type Source = fn(&mut Vec<TelemPoint>, f64) -> Result<(), Error>;
+
+const SOURCES: &[(&str, Source)] = &[
+    ("memory", memory),
+    ("load", load),
+    ("cpu", cpu),
+    ("uptime", uptime),
+    ("disk", disk),
+    ("processes", processes),
+    ("file_descriptors", file_descriptors),
+    ("soc_temp", soc_temp),
+];
+
+// CPU time counters from the previous collection (total, idle), so that we can report the
+// utilization since then
+static LAST_CPU: Mutex<Option<(u64, u64)>> = Mutex::new(None);
+
+pub struct Obc;
+
+impl Collector for Obc {
+    fn name(&self) -> &str {
+        "obc"
+    }
+
+    fn subsystem(&self) -> &str {
+        "OBC"
+    }
+
+    fn default_schedule(&self) -> Schedule {
+        Schedule::new(60, 5)
+    }
+
+    fn query(&self, telem_vec: &mut Vec<TelemPoint>) -> Result<(), Error> {
+        let timestamp = timestamp_now();
+
+        // Each source is independent, so one failing shouldn't keep us from storing the others
+        let mut failed = vec![];
+        for (name, source) in SOURCES {
+            if let Err(error) = source(telem_vec, timestamp) {
+                warn!("Failed to get OBC {} telemetry: {}", name, error);
+                failed.push(*name);
+            }
+        }
+
+        if !failed.is_empty() {
+            bail!("Failed to get OBC telemetry: {}", failed.join(", "));
+        }
+
+        Ok(())
+    }
+}
+
+fn memory(telem_vec: &mut Vec<TelemPoint>, timestamp: f64) -> Result<(), Error> {
     let service = ServiceConfig::new("monitor-service");
 
     let result = query(&service, OBC_TELEMETRY, Some(Duration::from_secs(1)))?;
 
-    let mut telem_vec: Vec<(String, String)> = vec![];
     let telemetry = &result["memInfo"].as_object();
 
     // Auto-convert returned JSON into a flat key-value vector
     if let Some(data) = telemetry {
-        process_json(&mut telem_vec, data, "memory_".to_owned());
+        process_json(telem_vec, data, "memory_".to_owned(), timestamp);
     }
 
-    // Send all the telemetry to the telemetry database
-    send_telem("OBC", telem_vec);
+    Ok(())
+}
+
+// 1, 5 and 15 minute load averages
+// /proc/loadavg: "0.12 0.08 0.03 1/95 1234"
+fn load(telem_vec: &mut Vec<TelemPoint>, timestamp: f64) -> Result<(), Error> {
+    let contents = fs::read_to_string("/proc/loadavg")?;
+    let mut fields = contents.split_whitespace();
+
+    for name in ["load_1m", "load_5m", "load_15m"].iter() {
+        let value: f64 = fields
+            .next()
+            .ok_or_else(|| format_err!("Missing {}", name))?
+            .parse()?;
+        telem_vec.push(TelemPoint::new(*name, value, timestamp));
+    }
+
+    Ok(())
+}
+
+// Percentage of time the CPU was busy since the last collection
+// /proc/stat: "cpu  user nice system idle iowait irq softirq steal ..."
+fn cpu(telem_vec: &mut Vec<TelemPoint>, timestamp: f64) -> Result<(), Error> {
+    let contents = fs::read_to_string("/proc/stat")?;
+    let counters: Vec<u64> = contents
+        .lines()
+        .find(|line| line.starts_with("cpu "))
+        .ok_or_else(|| format_err!("Missing CPU totals"))?
+        .split_whitespace()
+        .skip(1)
+        .filter_map(|field| field.parse().ok())
+        .collect();
+
+    if counters.len() < 5 {
+        bail!("Malformed CPU totals");
+    }
+
+    // Time spent waiting on IO counts as idle time
+    let total: u64 = counters.iter().sum();
+    let idle = counters[3] + counters[4];
+
+    // The first time through, this is the utilization since boot
+    let mut last = LAST_CPU.lock().unwrap_or_else(PoisonError::into_inner);
+    let (last_total, last_idle) = last.unwrap_or((0, 0));
+    *last = Some((total, idle));
+
+    let total_delta = total.saturating_sub(last_total);
+    if total_delta == 0 {
+        return Ok(());
+    }
+    let idle_delta = idle.saturating_sub(last_idle).min(total_delta);
+
+    let used = 100.0 * (total_delta - idle_delta) as f64 / total_delta as f64;
+    telem_vec.push(TelemPoint::new("cpu_used_pct", used, timestamp));
+
+    Ok(())
+}
+
+// Seconds since boot
+fn uptime(telem_vec: &mut Vec<TelemPoint>, timestamp: f64) -> Result<(), Error> {
+    let contents = fs::read_to_string("/proc/uptime")?;
+    let uptime: f64 = contents
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| format_err!("Missing uptime"))?
+        .parse()?;
+
+    telem_vec.push(TelemPoint::new("uptime", uptime, timestamp));
+
+    Ok(())
+}
+
+// Size and usage of each of the eMMC's partitions
+fn disk(telem_vec: &mut Vec<TelemPoint>, timestamp: f64) -> Result<(), Error> {
+    let mut missing = vec![];
+
+    for (name, partition) in PARTITIONS.iter() {
+        let usage = ["mmcblk1", "mmcblk0"]
+            .iter()
+            .find_map(|disk| df(&format!("/dev/{}p{}", disk, partition)));
+
+        match usage {
+            Some((size, used, percent)) => {
+                telem_vec.push(TelemPoint::new(
+                    format!("disk_{}_size", name),
+                    size,
+                    timestamp,
+                ));
+                telem_vec.push(TelemPoint::new(
+                    format!("disk_{}_used", name),
+                    used,
+                    timestamp,
+                ));
+                telem_vec.push(TelemPoint::new(
+                    format!("disk_{}_used_pct", name),
+                    percent,
+                    timestamp,
+                ));
+            }
+            None => missing.push(*name),
+        }
+    }
+
+    if !missing.is_empty() {
+        bail!("No usage info for {}", missing.join(", "));
+    }
+
+    Ok(())
+}
+
+// Get the size and used space (in kB), and the percent in use, of a mounted partition.
+// Returns `None` if the device doesn't exist or isn't mounted
+fn df(device: &str) -> Option<(u64, u64, u64)> {
+    let output = Command::new("df").arg(device).output().ok()?;
+    if !output.stderr.is_empty() {
+        return None;
+    }
+
+    // The last line of the output is the partition's usage:
+    // /dev/mmcblk0p4  3558576  157136  3201416   5% /home
+    // If the device isn't mounted, `df` reports on the filesystem holding the device node instead
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let fields: Vec<&str> = stdout.lines().last()?.split_whitespace().collect();
+    if fields.len() < 6 || fields[0] != device {
+        return None;
+    }
+
+    let size = fields[1].parse().ok()?;
+    let used = fields[2].parse().ok()?;
+    let percent = fields[4].trim_end_matches('%').parse().ok()?;
+
+    Some((size, used, percent))
+}
+
+// Number of running processes, along with the ones using the most memory
+fn processes(telem_vec: &mut Vec<TelemPoint>, timestamp: f64) -> Result<(), Error> {
+    let top_count = ServiceConfig::new("telem-app")
+        .get("obc")
+        .and_then(|obc| obc.get("top_processes").and_then(|val| val.as_integer()))
+        .map(|val| val.max(0) as usize)
+        .unwrap_or(DEFAULT_TOP_PROCESSES);
+
+    let mut count: u64 = 0;
+    // (resident memory in kB, pid, name)
+    let mut usage: Vec<(u64, u64, String)> = vec![];
+
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        let pid: u64 = match entry.file_name().to_string_lossy().parse() {
+            Ok(pid) => pid,
+            Err(_) => continue,
+        };
+
+        // The process might exit while we're looking at it
+        let status = match fs::read_to_string(entry.path().join("status")) {
+            Ok(status) => status,
+            Err(_) => continue,
+        };
+
+        count += 1;
+
+        let mut name = String::new();
+        let mut rss = None;
+        for line in status.lines() {
+            if let Some(value) = line.strip_prefix("Name:") {
+                name = value.trim().to_owned();
+            } else if let Some(value) = line.strip_prefix("VmRSS:") {
+                // "VmRSS:	    1234 kB"
+                rss = value
+                    .split_whitespace()
+                    .next()
+                    .and_then(|val| val.parse::<u64>().ok());
+            }
+        }
+
+        // Kernel threads don't have any memory of their own
+        if let Some(rss) = rss {
+            usage.push((rss, pid, name));
+        }
+    }
+
+    telem_vec.push(TelemPoint::new("process_count", count, timestamp));
+
+    usage.sort_by(|a, b| b.cmp(a));
+    for (index, (rss, pid, name)) in usage.into_iter().take(top_count).enumerate() {
+        let prefix = format!("top_memory_{}", index + 1);
+        telem_vec.push(TelemPoint::new(format!("{}_name", prefix), name, timestamp));
+        telem_vec.push(TelemPoint::new(format!("{}_pid", prefix), pid, timestamp));
+        telem_vec.push(TelemPoint::new(format!("{}_rss", prefix), rss, timestamp));
+    }
+
+    Ok(())
+}
+
+// Number of file descriptors open across the whole system
+// /proc/sys/fs/file-nr: "allocated unused maximum"
+fn file_descriptors(telem_vec: &mut Vec<TelemPoint>, timestamp: f64) -> Result<(), Error> {
+    let contents = fs::read_to_string("/proc/sys/fs/file-nr")?;
+    let open: u64 = contents
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| format_err!("Missing file descriptor count"))?
+        .parse()?;
+
+    telem_vec.push(TelemPoint::new("open_fds", open, timestamp));
+
+    Ok(())
+}
+
+// The AM335x's internal temperature sensor, reported in millidegrees C
+fn soc_temp(telem_vec: &mut Vec<TelemPoint>, timestamp: f64) -> Result<(), Error> {
+    let contents = fs::read_to_string(SOC_TEMP)?;
+    let millidegrees: i64 = contents.trim().parse()?;
+
+    telem_vec.push(TelemPoint::new(
+        "soc_temp",
+        millidegrees as f64 / 1000.0,
+        timestamp,
+    ));
 
     Ok(())
 }
@@ -14,11 +14,33 @@
 // limitations under the License.
 //
 
-// Gather telemetry from the NovAtel OEM7
+// Gather telemetry from the NovAtel OEM6
+//
+// Along with the nominal telemetry, we keep:
+//
+// - The receiver's error and status event messages (`LOG_ERROR_DATA`, enabled by deploy-app),
+//   which are stored as `gps_error` events
+// - Any new receiver status error flags, which are stored as `gps_status_error` events
+// - The quality of the last fix
+//
+// Events are stamped with the receiver's own time, from its latest position log, rather than when
+// we happened to collect them. The service doesn't pass on the time of each event log, so this is
+// the closest we can get.
+//
+// The service doesn't report how many satellites are being tracked. Tracking is recorded through
+// the solution and time statuses in `lockStatus` instead (ex. `lockStatus_positionType`)
 
-use crate::telem_db::{process_json, send_telem};
+use crate::collector::Collector;
+use crate::events::{event, EVENT_SUBSYSTEM};
+use crate::orbit;
+use crate::point::TelemPoint;
+use crate::schedule::Schedule;
+use crate::telem_db::{process_json, send_telem, timestamp_now};
 use failure::Error;
 use kubos_app::*;
+use log::*;
+use power_state::Unit;
+use std::sync::{Mutex, PoisonError};
 use std::time::Duration;
 
 // Note: Debug telemetry is omitted because it is only version/model info,
@@ -54,58 +76,341 @@ const OEM_TELEMETRY: &str = r#"{
     }
 }"#;
 
-const OEM_POWER: &str = r#"
-    mutation {
-        passthrough(module: "aim2", command: "GPS:POW ON") {
-            status,
-            command
+// NovAtel time status codes. The higher the code, the more the receiver's time can be trusted
+const TIME_STATUS: &[(&str, u8)] = &[
+    ("UNKNOWN", 20),
+    ("APPROXIMATE", 60),
+    ("COARSE_ADJUSTING", 80),
+    ("COARSE", 100),
+    ("COARSE_STEERING", 120),
+    ("FREE_WHEELING", 130),
+    ("FINE_ADJUSTING", 140),
+    ("FINE", 160),
+    ("FINE_BACKUP_STEERING", 170),
+    ("FINE_STEERING", 180),
+    ("SAT_TIME", 200),
+];
+
+// Times reported as GPS week and milliseconds. Each one also gets stored as a Unix time, named
+// `<prefix>_utc`
+const GPS_TIMES: &[&str] = &["lockInfo_time", "lockStatus_time"];
+
+// Receiver status error flags which were set during the previous collection
+static STATUS_ERRORS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+pub struct Oem6;
+
+impl Collector for Oem6 {
+    fn name(&self) -> &str {
+        "oem6"
+    }
+
+    fn subsystem(&self) -> &str {
+        "OEM"
+    }
+
+    fn default_schedule(&self) -> Schedule {
+        Schedule::new(60, 10)
+    }
+
+    fn counters(&self) -> &[&str] {
+        &["lockInfo_time_ms"]
+    }
+
+    // Make sure the OEM is on and able to communicate with us, unless the ground has
+    // commanded it off
+    fn prepare(&self) -> Result<bool, Error> {
+        power_state::ensure(Unit::Gps)
+    }
+
+    fn post_process(&self, mut telem_vec: Vec<TelemPoint>) -> Vec<TelemPoint> {
+        let mut converted = vec![];
+
+        for prefix in GPS_TIMES {
+            let find = |suffix: &str| {
+                let name = format!("{}_{}", prefix, suffix);
+                telem_vec.iter().find(|point| point.parameter == name)
+            };
+
+            if let (Some(week), Some(ms)) = (find("week"), find("ms")) {
+                // A week of 0 means the receiver doesn't know what time it is yet
+                if let (Some(week_val), Some(ms_val)) = (week.value.as_f64(), ms.value.as_f64()) {
+                    if week_val > 0.0 {
+                        converted.push(TelemPoint::new(
+                            format!("{}_utc", prefix),
+                            gps_time::week_ms_to_unix(week_val, ms_val),
+                            week.timestamp,
+                        ));
+                    }
+                }
+            }
+        }
+
+        telem_vec.extend(converted);
+        telem_vec
+    }
+
+    fn query(&self, telem_vec: &mut Vec<TelemPoint>) -> Result<(), Error> {
+        let service = ServiceConfig::new("novatel-oem6-service");
+
+        // Get all the basic telemetry
+        let timestamp = timestamp_now();
+        let result = query(&service, OEM_TELEMETRY, Some(Duration::from_secs(2)))?;
+
+        let nominal = &result["telemetry"]["nominal"].as_object();
+
+        // Auto-convert returned JSON into a flat key-value vector
+        if let Some(data) = nominal {
+            process_json(telem_vec, data, "".to_owned(), timestamp);
         }
+
+        // Give the orbit propagator the latest fix to work from
+        orbit::record_fix(&result["telemetry"]["nominal"]["lockInfo"]);
+
+        let log_time = receiver_time(&result["telemetry"]["nominal"]["lockStatus"]["time"])
+            .unwrap_or_else(|| {
+                debug!("Receiver time unknown. Stamping events with the collection time");
+                timestamp
+            });
+        let mut events = receiver_errors(&result["errors"], log_time);
+        events.extend(status_errors(
+            &result["telemetry"]["nominal"]["systemStatus"]["errors"],
+            log_time,
+        ));
+        if !events.is_empty() {
+            send_telem(EVENT_SUBSYSTEM, events);
+        }
+
+        fix_quality(
+            telem_vec,
+            &result["telemetry"]["nominal"]["lockStatus"],
+            timestamp,
+        );
+        fix_age(telem_vec, &result["telemetry"]["nominal"], timestamp);
+
+        Ok(())
+    }
+}
+
+// Unix time of one of the receiver's logs, from its GPS week and milliseconds.
+// A week of 0 means the receiver doesn't know what time it is yet
+fn receiver_time(time: &serde_json::Value) -> Option<f64> {
+    let week = time["week"].as_f64()?;
+    let ms = time["ms"].as_f64()?;
+
+    if week > 0.0 {
+        Some(gps_time::week_ms_to_unix(week, ms))
+    } else {
+        None
     }
-"#;
+}
+
+// Error and status event messages reported by the receiver since the last collection
+fn receiver_errors(errors: &serde_json::Value, timestamp: f64) -> Vec<TelemPoint> {
+    errors
+        .as_array()
+        .map(|errors| {
+            errors
+                .iter()
+                .filter_map(|error| error.as_str())
+                .map(|error| event("gps_error", error.to_owned(), timestamp))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Receiver status error flags which weren't set during the previous collection
+fn status_errors(errors: &serde_json::Value, timestamp: f64) -> Vec<TelemPoint> {
+    let current: Vec<String> = match errors.as_array() {
+        Some(errors) => errors
+            .iter()
+            .filter_map(|error| error.as_str().map(|error| error.to_owned()))
+            .collect(),
+        // We don't know what the flags are, so don't report anything
+        None => return vec![],
+    };
+
+    let mut previous = STATUS_ERRORS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let events = current
+        .iter()
+        .filter(|error| !previous.contains(error))
+        .map(|error| {
+            event(
+                "gps_status_error",
+                format!("Receiver status error set: {}", error),
+                timestamp,
+            )
+        })
+        .collect();
+
+    *previous = current;
+
+    events
+}
+
+// Look up the NovAtel code for one of the receiver's time statuses (ex. 180 for "FINE_STEERING")
+pub fn time_status_code(status: &str) -> Option<u8> {
+    TIME_STATUS
+        .iter()
+        .find(|(name, _)| *name == status)
+        .map(|(_, code)| *code)
+}
 
-const OEM_COMM: &str = r#"
-    mutation {
-        passthrough(module: "aim2", command: "GPS:COMM UART3") {
-            status,
-            command
+// How much the receiver's time can be trusted (see `TIME_STATUS`), and whether it currently
+// has a position solution
+fn fix_quality(telem_vec: &mut Vec<TelemPoint>, lock_status: &serde_json::Value, timestamp: f64) {
+    if let Some(status) = lock_status["timeStatus"].as_str() {
+        if let Some(code) = time_status_code(status) {
+            telem_vec.push(TelemPoint::new("time_quality", u64::from(code), timestamp));
         }
     }
-"#;
 
-const OEM_PASS: &str = r#"
-    mutation {
-        passthrough(module: "aim2", command: "GPS:PASS ON") {
-            status,
-            command
+    if let Some(status) = lock_status["positionStatus"].as_str() {
+        telem_vec.push(TelemPoint::new(
+            "fix_valid",
+            status == "SOL_COMPUTED",
+            timestamp,
+        ));
+    }
+}
+
+// Seconds between the last good fix (`lockInfo`) and the receiver's latest position log
+// (`lockStatus`)
+fn fix_age(telem_vec: &mut Vec<TelemPoint>, nominal: &serde_json::Value, timestamp: f64) {
+    // A week of 0 means the receiver hasn't had a fix yet
+    let gps_seconds = |time: &serde_json::Value| -> Option<f64> {
+        let week = time["week"].as_f64()?;
+        if week > 0.0 {
+            Some(gps_time::week_ms_to_gps(week, time["ms"].as_f64()?))
+        } else {
+            None
+        }
+    };
+
+    if let (Some(fix), Some(latest)) = (
+        gps_seconds(&nominal["lockInfo"]["time"]),
+        gps_seconds(&nominal["lockStatus"]["time"]),
+    ) {
+        if latest >= fix {
+            telem_vec.push(TelemPoint::new("fix_age", latest - fix, timestamp));
         }
     }
-"#;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point::TelemValue;
+    use serde_json::json;
+
+    fn nominal() -> serde_json::Value {
+        json!({
+            "lockInfo": {
+                "position": [-2_694_045.0, -4_293_642.0, 3_857_878.0],
+                "time": { "ms": 3_600_000, "week": 2048 },
+                "velocity": [1.5, -2.5, 7_600.0]
+            },
+            "lockStatus": {
+                "positionStatus": "SOL_COMPUTED",
+                "positionType": "SINGLE",
+                "time": { "ms": 3_630_000, "week": 2048 },
+                "timeStatus": "FINE_STEERING",
+                "velocityStatus": "SOL_COMPUTED",
+                "velocityType": "DOPPLER_VELOCITY"
+            },
+            "systemStatus": { "errors": [], "status": ["CPU_OVERLOAD"] }
+        })
+    }
 
-pub fn get_telem() -> Result<(), Error> {
-    // Make sure the OEM is on and able to communicate with us
-    let service = ServiceConfig::new("pumpkin-mcu-service");
+    fn value<'a>(telem_vec: &'a [TelemPoint], parameter: &str) -> &'a TelemValue {
+        &telem_vec
+            .iter()
+            .find(|point| point.parameter == parameter)
+            .unwrap_or_else(|| panic!("{} missing", parameter))
+            .value
+    }
 
-    let _ = query(&service, OEM_POWER, Some(Duration::from_millis(500)))?;
+    #[test]
+    fn lock_status_is_flattened() {
+        let mut telem_vec = vec![];
+        let nominal = nominal();
+        process_json(
+            &mut telem_vec,
+            nominal["lockStatus"].as_object().unwrap(),
+            "lockStatus_".to_owned(),
+            100.0,
+        );
 
-    let _ = query(&service, OEM_COMM, Some(Duration::from_millis(500)))?;
+        assert_eq!(telem_vec.len(), 7);
+        assert_eq!(
+            value(&telem_vec, "lockStatus_positionType"),
+            &TelemValue::Enum("SINGLE".to_owned())
+        );
+        assert_eq!(
+            value(&telem_vec, "lockStatus_time_week"),
+            &TelemValue::Int(2048)
+        );
+        assert!(telem_vec.iter().all(|point| point.timestamp == 100.0));
 
-    let _ = query(&service, OEM_PASS, Some(Duration::from_millis(500)))?;
+        // The nominal telemetry ends up with the same names
+        let mut nominal_vec = vec![];
+        process_json(
+            &mut nominal_vec,
+            nominal.as_object().unwrap(),
+            "".to_owned(),
+            100.0,
+        );
+        for point in telem_vec.iter() {
+            assert_eq!(value(&nominal_vec, &point.parameter), &point.value);
+        }
+        assert_eq!(
+            value(&nominal_vec, "systemStatus_status_0"),
+            &TelemValue::Enum("CPU_OVERLOAD".to_owned())
+        );
+    }
 
-    let service = ServiceConfig::new("novatel-oem6-service");
+    #[test]
+    fn fix_quality_and_age() {
+        let mut telem_vec = vec![];
+        let nominal = nominal();
+        fix_quality(&mut telem_vec, &nominal["lockStatus"], 100.0);
+        fix_age(&mut telem_vec, &nominal, 100.0);
 
-    // Get all the basic telemetry
-    let result = query(&service, OEM_TELEMETRY, Some(Duration::from_secs(2)))?;
+        assert_eq!(value(&telem_vec, "time_quality"), &TelemValue::Int(180));
+        assert_eq!(value(&telem_vec, "fix_valid"), &TelemValue::Bool(true));
+        assert_eq!(value(&telem_vec, "fix_age"), &TelemValue::Float(30.0));
 
-    let mut telem_vec: Vec<(String, String)> = vec![];
-    let nominal = &result["telemetry"]["nominal"].as_object();
+        // No fix yet
+        let mut telem_vec = vec![];
+        let mut nominal = nominal;
+        nominal["lockInfo"]["time"]["week"] = json!(0);
+        nominal["lockStatus"]["positionStatus"] = json!("INSUFFICIENT_OBS");
+        fix_quality(&mut telem_vec, &nominal["lockStatus"], 100.0);
+        fix_age(&mut telem_vec, &nominal, 100.0);
 
-    // Auto-convert returned JSON into a flat key-value vector
-    if let Some(data) = nominal {
-        process_json(&mut telem_vec, data, "".to_owned());
+        assert_eq!(value(&telem_vec, "fix_valid"), &TelemValue::Bool(false));
+        assert!(telem_vec.iter().all(|point| point.parameter != "fix_age"));
     }
 
-    // Send it to the telemetry database
-    send_telem("OEM", telem_vec);
+    #[test]
+    fn events_use_receiver_time() {
+        // GPS week 2048 started at 2019-04-07 00:00:00 GPS time, 18 seconds ahead of UTC
+        let log_time = receiver_time(&json!({ "ms": 3_600_000, "week": 2048 })).unwrap();
+        assert_eq!(log_time, 1_554_595_182.0 + 3600.0);
+        assert_eq!(receiver_time(&json!({ "ms": 3_600_000, "week": 0 })), None);
+        assert_eq!(receiver_time(&json!({})), None);
 
-    Ok(())
+        let events = receiver_errors(&json!(["RxStatusEvent: Antenna open"]), log_time);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].parameter, "gps_error");
+        assert_eq!(events[0].timestamp, log_time);
+
+        // Only newly-set flags are reported
+        let events = status_errors(&json!(["TEMP_WARNING"]), log_time);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].timestamp, log_time);
+        assert!(status_errors(&json!(["TEMP_WARNING"]), log_time + 60.0).is_empty());
+        assert!(status_errors(&json!(null), log_time).is_empty());
+    }
 }
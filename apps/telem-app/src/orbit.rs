@@ -0,0 +1,798 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Onboard orbit propagation
+//
+// Whenever the OEM6 has a good fix, its position and velocity become our reference state. When
+// it doesn't (ex. it's been commanded off), we propagate the last good state forward, so that we
+// still know roughly where we are.
+//
+// States are propagated in an Earth-centered inertial frame, using a fixed-step RK4 integrator
+// with two-body gravity plus J2. Precession, nutation, polar motion and drag are all ignored, so
+// the results drift by a few km per day, which is plenty for planning passes and predicting
+// eclipses.
+//
+// A TLE can also be uploaded. Its mean elements are treated as osculating elements and fed into
+// the same propagator rather than SGP4, so it's only an approximation, but it's still much better
+// than nothing if the GPS has been off for a long time. Whichever of the latest GPS fix and the
+// TLE is newer is used.
+//
+// [telem-app.orbit]
+// # Location of the uploaded TLE (two lines, optionally preceded by a name line). TLEs with bad
+// # checksums are rejected
+// tle_path = "/home/system/etc/orbit.tle"
+// # Integration step, in seconds
+// step = 10
+// # Don't propagate states older than this many seconds
+// max_age = 604800
+
+use crate::collector::Collector;
+use crate::point::TelemPoint;
+use crate::schedule::Schedule;
+use crate::telem_db::timestamp_now;
+use failure::{bail, format_err, Error};
+use kubos_app::*;
+use log::*;
+use std::f64::consts::PI;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+// WGS-84 / EGM-96
+const MU: f64 = 3.986_004_418e14;
+const EARTH_RADIUS: f64 = 6_378_137.0;
+const FLATTENING: f64 = 1.0 / 298.257_223_563;
+const J2: f64 = 1.082_626_68e-3;
+const EARTH_ROTATION: f64 = 7.292_115e-5;
+
+const DEFAULT_TLE_PATH: &str = "/home/system/etc/orbit.tle";
+const DEFAULT_STEP: f64 = 10.0;
+const DEFAULT_MAX_AGE: f64 = 7.0 * 86400.0;
+
+type Vector = [f64; 3];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Source {
+    Gps,
+    Tle,
+}
+
+impl Source {
+    fn name(self) -> &'static str {
+        match self {
+            Source::Gps => "GPS",
+            Source::Tle => "TLE",
+        }
+    }
+}
+
+// Inertial position (m) and velocity (m/s) at a particular Unix time
+#[derive(Clone, Copy, Debug)]
+struct State {
+    epoch: f64,
+    position: Vector,
+    velocity: Vector,
+}
+
+// Where we've propagated to so far, so that we don't have to start from the reference state
+// every time
+struct Propagation {
+    source: Source,
+    source_epoch: f64,
+    state: State,
+}
+
+// The latest good GPS fix, converted to an inertial state
+static FIX: Mutex<Option<State>> = Mutex::new(None);
+// Whether we've tried to get the latest fix from the telemetry database since the app started
+static FIX_LOADED: AtomicBool = AtomicBool::new(false);
+
+static PROPAGATION: Mutex<Option<Propagation>> = Mutex::new(None);
+
+struct Config {
+    tle_path: String,
+    step: f64,
+    max_age: f64,
+}
+
+impl Config {
+    fn load() -> Config {
+        let config = ServiceConfig::new("telem-app").get("orbit");
+        let get = |key: &str| config.as_ref().and_then(|config| config.get(key).cloned());
+        let number = |key: &str| {
+            get(key).and_then(|val| {
+                val.as_float()
+                    .or_else(|| val.as_integer().map(|v| v as f64))
+            })
+        };
+
+        Config {
+            tle_path: get("tle_path")
+                .and_then(|val| val.as_str().map(|path| path.to_owned()))
+                .unwrap_or_else(|| DEFAULT_TLE_PATH.to_owned()),
+            step: number("step")
+                .filter(|step| *step > 0.0)
+                .unwrap_or(DEFAULT_STEP),
+            max_age: number("max_age").unwrap_or(DEFAULT_MAX_AGE),
+        }
+    }
+}
+
+pub struct Orbit;
+
+impl Collector for Orbit {
+    fn name(&self) -> &str {
+        "orbit"
+    }
+
+    fn subsystem(&self) -> &str {
+        "ORBIT"
+    }
+
+    fn default_schedule(&self) -> Schedule {
+        Schedule::new(60, 10)
+    }
+
+    fn query(&self, telem_vec: &mut Vec<TelemPoint>) -> Result<(), Error> {
+        let config = Config::load();
+        let now = timestamp_now();
+
+        let tle = match fs::read_to_string(&config.tle_path) {
+            Ok(contents) => match parse_tle(&contents) {
+                Ok(state) => Some(state),
+                Err(error) => {
+                    warn!("Ignoring TLE in {}: {}", config.tle_path, error);
+                    None
+                }
+            },
+            Err(_) => None,
+        };
+
+        let (source, reference) = match (latest_fix(), tle) {
+            (Some(fix), Some(tle)) if tle.epoch > fix.epoch => (Source::Tle, tle),
+            (Some(fix), _) => (Source::Gps, fix),
+            (None, Some(tle)) => (Source::Tle, tle),
+            (None, None) => bail!("No GPS fix or TLE to propagate from"),
+        };
+
+        let age = now - reference.epoch;
+        if age > config.max_age {
+            bail!(
+                "Latest {} state is too old to propagate ({:.0}s)",
+                source.name(),
+                age
+            );
+        }
+
+        let mut propagation = PROPAGATION.lock().unwrap_or_else(PoisonError::into_inner);
+        let start = match propagation.take() {
+            Some(previous)
+                if previous.source == source && previous.source_epoch == reference.epoch =>
+            {
+                previous.state
+            }
+            _ => reference,
+        };
+
+        let state = propagate(start, now, config.step);
+        *propagation = Some(Propagation {
+            source,
+            source_epoch: reference.epoch,
+            state,
+        });
+
+        let ecef = eci_to_ecef(state.position, gmst(now));
+        let (latitude, longitude, altitude) = geodetic(ecef);
+
+        for (name, vector) in [
+            ("eci_position", state.position),
+            ("eci_velocity", state.velocity),
+            ("ecef_position", ecef),
+        ]
+        .iter()
+        {
+            for (index, value) in vector.iter().enumerate() {
+                telem_vec.push(TelemPoint::new(format!("{}_{}", name, index), *value, now));
+            }
+        }
+
+        telem_vec.push(TelemPoint::new("latitude", latitude.to_degrees(), now));
+        telem_vec.push(TelemPoint::new("longitude", longitude.to_degrees(), now));
+        telem_vec.push(TelemPoint::new("altitude", altitude, now));
+        telem_vec.push(TelemPoint::new(
+            "eclipse",
+            in_eclipse(state.position, now),
+            now,
+        ));
+        telem_vec.push(TelemPoint::new("state_age", age, now));
+        telem_vec.push(TelemPoint::new("source", source.name(), now));
+
+        Ok(())
+    }
+}
+
+// Record the OEM's latest good fix (its `lockInfo` block)
+pub fn record_fix(lock_info: &serde_json::Value) {
+    let vector = |field: &str| -> Option<Vector> {
+        let values = lock_info[field].as_array()?;
+        Some([
+            values.first()?.as_f64()?,
+            values.get(1)?.as_f64()?,
+            values.get(2)?.as_f64()?,
+        ])
+    };
+
+    if let (Some(week), Some(ms), Some(position), Some(velocity)) = (
+        lock_info["time"]["week"].as_f64(),
+        lock_info["time"]["ms"].as_f64(),
+        vector("position"),
+        vector("velocity"),
+    ) {
+        if let Some(state) = fix_state(week, ms, position, velocity) {
+            *FIX.lock().unwrap_or_else(PoisonError::into_inner) = Some(state);
+        }
+    }
+}
+
+// Get the latest GPS fix. If the OEM hasn't given us one since the app started, we fall back to
+// the last one in the telemetry database
+fn latest_fix() -> Option<State> {
+    let mut fix = FIX.lock().unwrap_or_else(PoisonError::into_inner);
+
+    if fix.is_none() && !FIX_LOADED.swap(true, Ordering::Relaxed) {
+        match stored_fix() {
+            Ok(state) => *fix = state,
+            Err(error) => warn!("Failed to get last GPS fix from the database: {}", error),
+        }
+    }
+
+    *fix
+}
+
+fn stored_fix() -> Result<Option<State>, Error> {
+    let service = ServiceConfig::new("telemetry-service");
+    let latest = |parameter: &str| -> Result<f64, Error> {
+        let request = format!(
+            r#"{{
+                telemetry(subsystem: "OEM", parameter: "{}", limit: 1) {{
+                    value
+                }}
+            }}"#,
+            parameter
+        );
+        let result = query(&service, &request, Some(Duration::from_secs(1)))?;
+        result["telemetry"][0]["value"]
+            .as_str()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format_err!("No value for {}", parameter))
+    };
+
+    let week = latest("lockInfo_time_week")?;
+    let ms = latest("lockInfo_time_ms")?;
+    let mut position = [0.0; 3];
+    let mut velocity = [0.0; 3];
+    for index in 0..3 {
+        position[index] = latest(&format!("lockInfo_position_{}", index))?;
+        velocity[index] = latest(&format!("lockInfo_velocity_{}", index))?;
+    }
+
+    Ok(fix_state(week, ms, position, velocity))
+}
+
+// Convert a GPS fix (ECEF position and velocity) into an inertial state
+fn fix_state(week: f64, ms: f64, position: Vector, velocity: Vector) -> Option<State> {
+    // A week of 0 or an all-zero position means there's never been a fix
+    if week <= 0.0 || norm(position) == 0.0 {
+        return None;
+    }
+
+    let epoch = gps_time::week_ms_to_unix(week, ms);
+    let theta = gmst(epoch);
+
+    // The ECEF frame is rotating, so that needs to be added to the velocity
+    let rotation = [
+        -EARTH_ROTATION * position[1],
+        EARTH_ROTATION * position[0],
+        0.0,
+    ];
+
+    Some(State {
+        epoch,
+        position: rotate_z(position, -theta),
+        velocity: rotate_z(add(velocity, rotation), -theta),
+    })
+}
+
+// Parse a TLE's epoch and mean elements into an (approximate) inertial state
+fn parse_tle(contents: &str) -> Result<State, Error> {
+    let line1 = contents
+        .lines()
+        .find(|line| line.starts_with("1 "))
+        .ok_or_else(|| format_err!("Missing line 1"))?;
+    let line2 = contents
+        .lines()
+        .find(|line| line.starts_with("2 "))
+        .ok_or_else(|| format_err!("Missing line 2"))?;
+
+    for line in &[line1, line2] {
+        check_tle_line(line)?;
+    }
+
+    let field = |line: &str, start: usize, end: usize| -> Result<f64, Error> {
+        line.get(start..end)
+            .map(|field| field.trim())
+            .and_then(|field| field.parse().ok())
+            .ok_or_else(|| format_err!("Bad field at columns {}-{}", start + 1, end))
+    };
+
+    // Epoch: two-digit year and fractional day of the year
+    let year = field(line1, 18, 20)? as i64;
+    let year = if year < 57 { 2000 + year } else { 1900 + year };
+    let day = field(line1, 20, 32)?;
+    let epoch = (days_since_1970(year) as f64 + day - 1.0) * 86400.0;
+
+    let inclination = field(line2, 8, 16)?.to_radians();
+    let raan = field(line2, 17, 25)?.to_radians();
+    // Eccentricity has an implied leading decimal point
+    let eccentricity = field(line2, 26, 33)? * 1e-7;
+    let perigee = field(line2, 34, 42)?.to_radians();
+    let mean_anomaly = field(line2, 43, 51)?.to_radians();
+    let mean_motion = field(line2, 52, 63)? * 2.0 * PI / 86400.0;
+
+    if mean_motion <= 0.0 || eccentricity >= 1.0 {
+        bail!("Not a closed orbit");
+    }
+
+    let semi_major = (MU / (mean_motion * mean_motion)).cbrt();
+
+    // Solve Kepler's equation for the eccentric anomaly
+    let mut anomaly = mean_anomaly;
+    for _ in 0..20 {
+        anomaly -= (anomaly - eccentricity * anomaly.sin() - mean_anomaly)
+            / (1.0 - eccentricity * anomaly.cos());
+    }
+
+    let true_anomaly = 2.0
+        * ((1.0 + eccentricity).sqrt() * (anomaly / 2.0).sin())
+            .atan2((1.0 - eccentricity).sqrt() * (anomaly / 2.0).cos());
+    let radius = semi_major * (1.0 - eccentricity * anomaly.cos());
+    let speed = (MU / (semi_major * (1.0 - eccentricity * eccentricity))).sqrt();
+
+    // Position and velocity in the orbital plane, then rotated into the inertial frame
+    let position = [
+        radius * true_anomaly.cos(),
+        radius * true_anomaly.sin(),
+        0.0,
+    ];
+    let velocity = [
+        -speed * true_anomaly.sin(),
+        speed * (eccentricity + true_anomaly.cos()),
+        0.0,
+    ];
+    let to_inertial =
+        |vector: Vector| rotate_z(rotate_x(rotate_z(vector, -perigee), -inclination), -raan);
+
+    Ok(State {
+        epoch,
+        position: to_inertial(position),
+        velocity: to_inertial(velocity),
+    })
+}
+
+// Each TLE line is 69 characters, the last of which is a checksum: the sum of all of the digits
+// in the rest of the line, counting each minus sign as 1, modulo 10
+fn check_tle_line(line: &str) -> Result<(), Error> {
+    let line = line.trim_end();
+    if line.len() != 69 || !line.is_ascii() {
+        bail!("Line {} is not 69 characters long", &line[..1]);
+    }
+
+    let expected = line[68..]
+        .parse::<u32>()
+        .map_err(|_| format_err!("Line {} has no checksum", &line[..1]))?;
+    let sum: u32 = line[..68]
+        .chars()
+        .map(|elem| match elem {
+            '-' => 1,
+            _ => elem.to_digit(10).unwrap_or(0),
+        })
+        .sum();
+
+    if sum % 10 != expected {
+        bail!("Line {} has a bad checksum", &line[..1]);
+    }
+
+    Ok(())
+}
+
+// Days from 1970-01-01 to January 1st of the given year
+fn days_since_1970(year: i64) -> i64 {
+    365 * (year - 1970) + (year - 1969).div_euclid(4) - (year - 1901).div_euclid(100)
+        + (year - 1601).div_euclid(400)
+}
+
+// Propagate a state to the given time with fixed RK4 steps
+fn propagate(mut state: State, target: f64, step: f64) -> State {
+    while (target - state.epoch).abs() > 1e-6 {
+        let remaining = target - state.epoch;
+        let dt = remaining.signum() * remaining.abs().min(step);
+
+        let derivative = |position: Vector, velocity: Vector| (velocity, acceleration(position));
+
+        let (k1r, k1v) = derivative(state.position, state.velocity);
+        let (k2r, k2v) = derivative(
+            add(state.position, scale(k1r, dt / 2.0)),
+            add(state.velocity, scale(k1v, dt / 2.0)),
+        );
+        let (k3r, k3v) = derivative(
+            add(state.position, scale(k2r, dt / 2.0)),
+            add(state.velocity, scale(k2v, dt / 2.0)),
+        );
+        let (k4r, k4v) = derivative(
+            add(state.position, scale(k3r, dt)),
+            add(state.velocity, scale(k3v, dt)),
+        );
+
+        let weighted = |k1: Vector, k2: Vector, k3: Vector, k4: Vector| {
+            scale(
+                add(add(k1, scale(k2, 2.0)), add(scale(k3, 2.0), k4)),
+                dt / 6.0,
+            )
+        };
+
+        state = State {
+            epoch: state.epoch + dt,
+            position: add(state.position, weighted(k1r, k2r, k3r, k4r)),
+            velocity: add(state.velocity, weighted(k1v, k2v, k3v, k4v)),
+        };
+    }
+
+    state
+}
+
+// Two-body gravity plus the J2 perturbation
+fn acceleration(position: Vector) -> Vector {
+    let r = norm(position);
+    let z2 = (position[2] / r).powi(2);
+    let j2 = 1.5 * J2 * (EARTH_RADIUS / r).powi(2);
+    let factor = -MU / r.powi(3);
+
+    [
+        factor * position[0] * (1.0 - j2 * (5.0 * z2 - 1.0)),
+        factor * position[1] * (1.0 - j2 * (5.0 * z2 - 1.0)),
+        factor * position[2] * (1.0 - j2 * (5.0 * z2 - 3.0)),
+    ]
+}
+
+// Greenwich mean sidereal time, in radians. UT1 is close enough to UTC for our purposes
+fn gmst(unix: f64) -> f64 {
+    let centuries = (unix / 86400.0 + 2_440_587.5 - 2_451_545.0) / 36525.0;
+    let seconds = 67_310.548_41
+        + (876_600.0 * 3600.0 + 8_640_184.812_866) * centuries
+        + 0.093_104 * centuries.powi(2)
+        - 6.2e-6 * centuries.powi(3);
+
+    (seconds.rem_euclid(86400.0) / 240.0).to_radians()
+}
+
+fn eci_to_ecef(position: Vector, gmst: f64) -> Vector {
+    rotate_z(position, gmst)
+}
+
+// Geodetic latitude and longitude (radians) and altitude (m) above the WGS-84 ellipsoid
+fn geodetic(ecef: Vector) -> (f64, f64, f64) {
+    let e2 = FLATTENING * (2.0 - FLATTENING);
+    let longitude = ecef[1].atan2(ecef[0]);
+    let p = ecef[0].hypot(ecef[1]);
+
+    let mut latitude = ecef[2].atan2(p * (1.0 - e2));
+    for _ in 0..5 {
+        let n = EARTH_RADIUS / (1.0 - e2 * latitude.sin().powi(2)).sqrt();
+        latitude = (ecef[2] + e2 * n * latitude.sin()).atan2(p);
+    }
+
+    let altitude = p * latitude.cos() + ecef[2] * latitude.sin()
+        - EARTH_RADIUS * (1.0 - e2 * latitude.sin().powi(2)).sqrt();
+
+    (latitude, longitude, altitude)
+}
+
+// Check whether we're in the Earth's shadow, treating it as a cylinder
+fn in_eclipse(position: Vector, unix: f64) -> bool {
+    let sun = sun_direction(unix);
+    let along = dot(position, sun);
+
+    along < 0.0 && norm(add(position, scale(sun, -along))) < EARTH_RADIUS
+}
+
+// Unit vector from the Earth to the Sun, from the Astronomical Almanac's low-precision formula
+fn sun_direction(unix: f64) -> Vector {
+    let days = unix / 86400.0 + 2_440_587.5 - 2_451_545.0;
+    let mean_longitude = (280.460 + 0.985_647_4 * days).to_radians();
+    let mean_anomaly = (357.528 + 0.985_600_3 * days).to_radians();
+    let longitude = mean_longitude
+        + (1.915_f64.to_radians()) * mean_anomaly.sin()
+        + (0.020_f64.to_radians()) * (2.0 * mean_anomaly).sin();
+    let obliquity = (23.439 - 0.000_000_4 * days).to_radians();
+
+    [
+        longitude.cos(),
+        obliquity.cos() * longitude.sin(),
+        obliquity.sin() * longitude.sin(),
+    ]
+}
+
+// Rotate a vector's frame about the z-axis by `angle` radians
+fn rotate_z(vector: Vector, angle: f64) -> Vector {
+    let (sin, cos) = angle.sin_cos();
+    [
+        cos * vector[0] + sin * vector[1],
+        -sin * vector[0] + cos * vector[1],
+        vector[2],
+    ]
+}
+
+// Rotate a vector's frame about the x-axis by `angle` radians
+fn rotate_x(vector: Vector, angle: f64) -> Vector {
+    let (sin, cos) = angle.sin_cos();
+    [
+        vector[0],
+        cos * vector[1] + sin * vector[2],
+        -sin * vector[1] + cos * vector[2],
+    ]
+}
+
+fn add(a: Vector, b: Vector) -> Vector {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn scale(vector: Vector, factor: f64) -> Vector {
+    [vector[0] * factor, vector[1] * factor, vector[2] * factor]
+}
+
+fn dot(a: Vector, b: Vector) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn norm(vector: Vector) -> f64 {
+    dot(vector, vector).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A published ISS TLE, from September 2008
+    const ISS: &str = "ISS (ZARYA)
+1 25544U 98067A   08264.51782528 -.00002182  00000-0 -11606-4 0  2927
+2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563537";
+    // 2008-09-20 12:25:40.104 UTC
+    const ISS_EPOCH: f64 = 1_221_913_540.104;
+    const ISS_PERIOD: f64 = 86400.0 / 15.721_253_91;
+
+    // Replace the characters of a TLE line starting at `column` (counting from 0), and fix up
+    // its checksum
+    fn edit(line: &str, column: usize, text: &str) -> String {
+        let mut line = line.to_owned();
+        line.replace_range(column..column + text.len(), text);
+        let sum: u32 = line[..68]
+            .chars()
+            .map(|elem| match elem {
+                '-' => 1,
+                _ => elem.to_digit(10).unwrap_or(0),
+            })
+            .sum();
+        line.replace_range(68..69, &(sum % 10).to_string());
+        line
+    }
+
+    fn iss_lines() -> (&'static str, &'static str) {
+        let mut lines = ISS.lines().skip(1);
+        (lines.next().unwrap(), lines.next().unwrap())
+    }
+
+    fn position(state: &State) -> (f64, f64, f64) {
+        let (latitude, longitude, altitude) =
+            geodetic(eci_to_ecef(state.position, gmst(state.epoch)));
+        (latitude.to_degrees(), longitude.to_degrees(), altitude)
+    }
+
+    #[test]
+    fn parses_tle() {
+        let state = parse_tle(ISS).unwrap();
+        assert!((state.epoch - ISS_EPOCH).abs() < 1e-3);
+
+        // The name line is optional
+        let (line1, line2) = iss_lines();
+        let state = parse_tle(&format!("{}\n{}\n", line1, line2)).unwrap();
+        assert!((state.epoch - ISS_EPOCH).abs() < 1e-3);
+
+        // Roughly 350 km up, at about 7.7 km/s
+        let (latitude, _, altitude) = position(&state);
+        assert!(latitude.abs() <= 51.7, "latitude {}", latitude);
+        assert!(
+            altitude > 330e3 && altitude < 375e3,
+            "altitude {}",
+            altitude
+        );
+        assert!((norm(state.velocity) - 7_700.0).abs() < 100.0);
+    }
+
+    #[test]
+    fn tle_epoch_years_roll_over() {
+        let (line1, line2) = iss_lines();
+        let epoch = |year: &str| {
+            let line1 = edit(line1, 18, year);
+            parse_tle(&format!("{}\n{}", line1, line2)).unwrap().epoch
+        };
+        let day = (264.517_825_28 - 1.0) * 86400.0;
+
+        // Two-digit years from 57 onwards are in the 1900s
+        assert_eq!(days_since_1970(1957), -4748);
+        assert_eq!(days_since_1970(2000), 10957);
+        assert_eq!(days_since_1970(2056), 31411);
+        assert_eq!(days_since_1970(2100), 47482);
+        assert!((epoch("57") - (-4748.0 * 86400.0 + day)).abs() < 1e-3);
+        assert!((epoch("99") - (10592.0 * 86400.0 + day)).abs() < 1e-3);
+        assert!((epoch("00") - (10957.0 * 86400.0 + day)).abs() < 1e-3);
+        assert!((epoch("56") - (31411.0 * 86400.0 + day)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn rejects_bad_tles() {
+        let (line1, line2) = iss_lines();
+
+        // Missing lines
+        assert!(parse_tle("").is_err());
+        assert!(parse_tle(line1).is_err());
+        assert!(parse_tle(line2).is_err());
+
+        // Bad checksums
+        let bad = line2.replace("15.72125391563537", "15.72125391563538");
+        assert!(parse_tle(&format!("{}\n{}", line1, bad)).is_err());
+        let bad = line1.replace("2927", "2926");
+        assert!(parse_tle(&format!("{}\n{}", bad, line2)).is_err());
+
+        // Truncated
+        assert!(parse_tle(&format!("{}\n{}", line1, &line2[..60])).is_err());
+
+        // Garbage in a field, even with a good checksum
+        let bad = edit(line2, 8, "51.6x16");
+        assert!(parse_tle(&format!("{}\n{}", line1, bad)).is_err());
+
+        // No mean motion
+        let bad = edit(line2, 52, " 0.00000000");
+        assert!(parse_tle(&format!("{}\n{}", line1, bad)).is_err());
+    }
+
+    #[test]
+    fn propagates_one_orbit() {
+        let start = parse_tle(ISS).unwrap();
+        let mut state = start;
+        let mut max_latitude: f64 = 0.0;
+
+        // Check where we are every minute
+        let mut time = start.epoch;
+        while time < start.epoch + ISS_PERIOD {
+            time = (time + 60.0).min(start.epoch + ISS_PERIOD);
+            state = propagate(state, time, DEFAULT_STEP);
+
+            let (latitude, _, altitude) = position(&state);
+            assert!(
+                altitude > 320e3 && altitude < 390e3,
+                "altitude {}",
+                altitude
+            );
+            max_latitude = max_latitude.max(latitude.abs());
+        }
+
+        // The ground track reaches the orbit's inclination, and no further
+        assert!((max_latitude - 51.64).abs() < 0.3, "max {}", max_latitude);
+
+        // After one period, we're back close to where we started. J2 shifts the orbit a little
+        assert!((state.epoch - (start.epoch + ISS_PERIOD)).abs() < 1e-6);
+        let distance = norm(add(state.position, scale(start.position, -1.0)));
+        assert!(distance < 100e3, "distance {}", distance);
+
+        // Propagating backwards gets us back to the start
+        let back = propagate(state, start.epoch, DEFAULT_STEP);
+        let distance = norm(add(back.position, scale(start.position, -1.0)));
+        assert!(distance < 1.0, "distance {}", distance);
+    }
+
+    #[test]
+    fn sidereal_time() {
+        // At J2000 (2000-01-01 12:00 UTC), GMST is 280.46062 degrees
+        let j2000 = 946_728_000.0;
+        assert!((gmst(j2000).to_degrees() - 280.460_62).abs() < 1e-3);
+
+        // A sidereal day is about 236 seconds shorter than a solar day
+        let drift = (gmst(j2000 + 86400.0) - gmst(j2000)).to_degrees();
+        assert!((drift - 0.985_65).abs() < 1e-3, "drift {}", drift);
+    }
+
+    #[test]
+    fn converts_to_geodetic() {
+        let polar_radius = EARTH_RADIUS * (1.0 - FLATTENING);
+
+        let (latitude, longitude, altitude) = geodetic([EARTH_RADIUS, 0.0, 0.0]);
+        assert!(latitude.abs() < 1e-9 && longitude.abs() < 1e-9 && altitude.abs() < 1e-3);
+
+        let (latitude, _, altitude) = geodetic([0.0, 0.0, polar_radius + 1000.0]);
+        assert!((latitude.to_degrees() - 90.0).abs() < 1e-9);
+        assert!((altitude - 1000.0).abs() < 1e-3);
+
+        // 45N 90W, 400 km up
+        let e2 = FLATTENING * (2.0 - FLATTENING);
+        let (lat, lon, alt): (f64, f64, f64) = (45f64.to_radians(), -90f64.to_radians(), 400e3);
+        let n = EARTH_RADIUS / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+        let ecef = [
+            (n + alt) * lat.cos() * lon.cos(),
+            (n + alt) * lat.cos() * lon.sin(),
+            (n * (1.0 - e2) + alt) * lat.sin(),
+        ];
+        let (latitude, longitude, altitude) = geodetic(ecef);
+        assert!((latitude - lat).abs() < 1e-9);
+        assert!((longitude - lon).abs() < 1e-9);
+        assert!((altitude - alt).abs() < 1e-3);
+
+        // The inertial frame lines up with the Earth-fixed one when GMST is 0
+        assert_eq!(eci_to_ecef([1.0, 2.0, 3.0], 0.0), [1.0, 2.0, 3.0]);
+        let rotated = eci_to_ecef([1.0, 0.0, 0.0], PI / 2.0);
+        assert!((rotated[1] + 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn eclipses() {
+        let time = ISS_EPOCH;
+        let sun = sun_direction(time);
+        assert!((norm(sun) - 1.0).abs() < 1e-9);
+
+        // Around the September equinox, the Sun is almost exactly over the equator
+        assert!(sun[2].abs() < 0.02);
+
+        let radius = EARTH_RADIUS + 400e3;
+        assert!(in_eclipse(scale(sun, -radius), time));
+        assert!(!in_eclipse(scale(sun, radius), time));
+
+        // Beside the Earth, and not in its shadow
+        let side = [-sun[1], sun[0], 0.0];
+        let side = scale(side, radius / norm(side));
+        assert!(!in_eclipse(side, time));
+        // Just inside and just outside the edge of the shadow, behind the Earth
+        let edge = |offset: f64| {
+            let side = scale(side, (EARTH_RADIUS + offset) / radius);
+            add(side, scale(sun, -radius))
+        };
+        assert!(in_eclipse(edge(-10e3), time));
+        assert!(!in_eclipse(edge(10e3), time));
+    }
+
+    #[test]
+    fn gps_fixes() {
+        assert!(fix_state(0.0, 1000.0, [7e6, 0.0, 0.0], [0.0, 7.5e3, 0.0]).is_none());
+        assert!(fix_state(2048.0, 1000.0, [0.0; 3], [0.0; 3]).is_none());
+
+        // Something sitting still on the ground is moving with the Earth
+        let state = fix_state(2048.0, 0.0, [EARTH_RADIUS, 0.0, 0.0], [0.0; 3]).unwrap();
+        assert_eq!(state.epoch, gps_time::week_ms_to_unix(2048.0, 0.0));
+        assert!((norm(state.velocity) - EARTH_ROTATION * EARTH_RADIUS).abs() < 1e-6);
+        assert!((norm(state.position) - EARTH_RADIUS).abs() < 1e-6);
+        let (_, longitude, _) = geodetic(eci_to_ecef(state.position, gmst(state.epoch)));
+        assert!(longitude.abs() < 1e-9);
+    }
+}
@@ -0,0 +1,217 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Typed telemetry points
+//
+// The telemetry database stores every value as a string, so we keep track of what kind of
+// value each point actually holds until the very last moment. When a point is serialized, the
+// value is written out exactly as it should be read back: numbers and booleans as-is, and
+// strings without any surrounding quotes.
+//
+// The telemetry service only stores a value for each point, so the kind of value and its unit are
+// stored as companion points, `<parameter>_kind` and `<parameter>_unit`. These rarely change, so
+// they're only sent the first time a parameter is seen, and again whenever they change.
+
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Mutex, PoisonError};
+
+// Kind and unit of a parameter, as last sent
+type Description = (&'static str, Option<&'static str>);
+
+// The description last sent for each subsystem's parameters
+static DESCRIBED: Mutex<BTreeMap<(String, String), Description>> = Mutex::new(BTreeMap::new());
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TelemValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    // A named state reported by a subsystem (ex. "SOL_COMPUTED")
+    Enum(String),
+    Str(String),
+}
+
+impl TelemValue {
+    // Convert a single JSON value returned by a subsystem.
+    // Returns `None` for `null`, since there's no measurement to store
+    pub fn from_json(value: &serde_json::Value) -> Option<TelemValue> {
+        let converted = match value {
+            serde_json::Value::Null => return None,
+            serde_json::Value::Bool(val) => TelemValue::Bool(*val),
+            serde_json::Value::Number(val) => match val.as_i64() {
+                Some(int) => TelemValue::Int(int),
+                None => TelemValue::Float(val.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(val) => TelemValue::from(val.as_str()),
+            // Anything more complicated is stored as its JSON representation
+            other => TelemValue::Str(other.to_string()),
+        };
+
+        Some(converted)
+    }
+
+    // Name of the kind of value, as stored in `<parameter>_kind` points
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TelemValue::Int(_) => "int",
+            TelemValue::Float(_) => "float",
+            TelemValue::Bool(_) => "bool",
+            TelemValue::Enum(_) => "enum",
+            TelemValue::Str(_) => "string",
+        }
+    }
+
+    // Numeric value of the point, for anything which needs to do math with it
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            TelemValue::Int(val) => Some(*val as f64),
+            TelemValue::Float(val) => Some(*val),
+            _ => None,
+        }
+    }
+}
+
+// Enumerated states come back as upper-case identifiers (ex. "FINE_STEERING", "NO_ERROR"), so we
+// use that to tell them apart from free-form strings
+impl From<&str> for TelemValue {
+    fn from(value: &str) -> Self {
+        let is_enum = value
+            .chars()
+            .next()
+            .map(|first| first.is_ascii_uppercase())
+            .unwrap_or(false)
+            && value
+                .chars()
+                .all(|elem| elem.is_ascii_uppercase() || elem.is_ascii_digit() || elem == '_');
+
+        if is_enum {
+            TelemValue::Enum(value.to_owned())
+        } else {
+            TelemValue::Str(value.to_owned())
+        }
+    }
+}
+
+impl From<String> for TelemValue {
+    fn from(value: String) -> Self {
+        TelemValue::from(value.as_str())
+    }
+}
+
+impl From<i64> for TelemValue {
+    fn from(value: i64) -> Self {
+        TelemValue::Int(value)
+    }
+}
+
+impl From<u64> for TelemValue {
+    fn from(value: u64) -> Self {
+        if value > i64::MAX as u64 {
+            TelemValue::Float(value as f64)
+        } else {
+            TelemValue::Int(value as i64)
+        }
+    }
+}
+
+impl From<f64> for TelemValue {
+    fn from(value: f64) -> Self {
+        TelemValue::Float(value)
+    }
+}
+
+impl From<bool> for TelemValue {
+    fn from(value: bool) -> Self {
+        TelemValue::Bool(value)
+    }
+}
+
+impl fmt::Display for TelemValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TelemValue::Int(val) => write!(f, "{}", val),
+            TelemValue::Float(val) => write!(f, "{}", val),
+            TelemValue::Bool(val) => write!(f, "{}", val),
+            TelemValue::Enum(val) | TelemValue::Str(val) => write!(f, "{}", val),
+        }
+    }
+}
+
+// A single telemetry value, along with the time it was measured
+#[derive(Clone, Debug)]
+pub struct TelemPoint {
+    pub parameter: String,
+    pub value: TelemValue,
+    // Engineering unit of the value (ex. "mV"), if known
+    pub unit: Option<&'static str>,
+    // Seconds since the Unix epoch
+    pub timestamp: f64,
+}
+
+impl TelemPoint {
+    pub fn new<P: Into<String>, V: Into<TelemValue>>(
+        parameter: P,
+        value: V,
+        timestamp: f64,
+    ) -> Self {
+        TelemPoint {
+            parameter: parameter.into(),
+            value: value.into(),
+            unit: None,
+            timestamp,
+        }
+    }
+
+    // Create the message the telemetry service expects for this point
+    pub fn to_message(&self, subsystem: &str) -> serde_json::Value {
+        json!({
+            "timestamp": self.timestamp,
+            "subsystem": subsystem,
+            "parameter": self.parameter,
+            "value": self.value.to_string()
+        })
+    }
+
+    // Create the `<parameter>_kind` and `<parameter>_unit` messages for this point, if they
+    // haven't already been sent with the same contents
+    pub fn describe(&self, subsystem: &str) -> Vec<serde_json::Value> {
+        let kind = self.value.kind();
+        let key = (subsystem.to_owned(), self.parameter.clone());
+
+        let mut described = DESCRIBED.lock().unwrap_or_else(PoisonError::into_inner);
+        if described.get(&key) == Some(&(kind, self.unit)) {
+            return vec![];
+        }
+        described.insert(key, (kind, self.unit));
+
+        let mut messages =
+            vec![
+                TelemPoint::new(format!("{}_kind", self.parameter), kind, self.timestamp)
+                    .to_message(subsystem),
+            ];
+
+        if let Some(unit) = self.unit {
+            messages.push(
+                TelemPoint::new(format!("{}_unit", self.parameter), unit, self.timestamp)
+                    .to_message(subsystem),
+            );
+        }
+
+        messages
+    }
+}
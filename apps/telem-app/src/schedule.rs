@@ -0,0 +1,181 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Run each telemetry collector on its own schedule
+//
+// Every collector gets its own thread, so one slow (or hung) subsystem can't hold up the
+// others. The schedule for each collector can be overridden in the `[telem-app]` section of
+// the system's config.toml file:
+//
+// [telem-app.eps]
+// enabled = true
+// interval = 300 # Seconds between the start of each collection
+// timeout = 290  # Seconds to wait for a collection to finish before reporting it as hung
+
+use crate::collector::{self, Collector};
+use crate::stats;
+use failure::Error;
+use kubos_app::ServiceConfig;
+use log::*;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Schedule {
+    pub enabled: bool,
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Schedule {
+    pub const fn new(interval: u64, timeout: u64) -> Schedule {
+        Schedule {
+            enabled: true,
+            interval: Duration::from_secs(interval),
+            timeout: Duration::from_secs(timeout),
+        }
+    }
+
+    // Get the schedule for a collector, using the given defaults for any values which aren't
+    // present in the config file
+    pub fn load(name: &str, default: Schedule) -> Schedule {
+        let config = ServiceConfig::new("telem-app");
+        let section = config.get(name);
+
+        let enabled = section
+            .as_ref()
+            .and_then(|section| section.get("enabled"))
+            .and_then(|val| val.as_bool())
+            .unwrap_or(default.enabled);
+
+        let interval = section
+            .as_ref()
+            .and_then(|section| section.get("interval"))
+            .and_then(|val| val.as_integer())
+            .map(|val| Duration::from_secs(val as u64))
+            .unwrap_or(default.interval);
+
+        let timeout = section
+            .as_ref()
+            .and_then(|section| section.get("timeout"))
+            .and_then(|val| val.as_integer())
+            .map(|val| Duration::from_secs(val as u64))
+            .unwrap_or(default.timeout);
+
+        Schedule {
+            enabled,
+            interval,
+            timeout,
+        }
+    }
+}
+
+// Kick off a thread which will run the collector at its requested interval
+pub fn spawn(collector: Arc<dyn Collector>) -> Option<thread::JoinHandle<()>> {
+    let name = collector.name().to_owned();
+    let schedule = Schedule::load(&name, collector.default_schedule());
+
+    if !schedule.enabled {
+        info!("{} telemetry collection disabled", name);
+        return None;
+    }
+
+    debug!("Starting {} telemetry collection: {:?}", name, schedule);
+
+    Some(thread::spawn(move || run(collector, schedule)))
+}
+
+fn run(collector: Arc<dyn Collector>, schedule: Schedule) {
+    // Results channel for a collection which has timed out, but hasn't finished yet
+    let mut pending: Option<Receiver<Result<(), Error>>> = None;
+
+    loop {
+        let start = Instant::now();
+
+        let still_running = match pending.as_ref().map(|rx| rx.try_recv()) {
+            Some(Err(TryRecvError::Empty)) => true,
+            Some(Ok(result)) => {
+                warn!(
+                    "Hung {} telemetry collection finished: {:?}",
+                    collector.name(),
+                    result
+                );
+                false
+            }
+            _ => false,
+        };
+
+        if still_running {
+            // We don't want to keep piling new requests on top of a subsystem which isn't
+            // answering the old ones
+            error!(
+                "{} telemetry collection is still hung. Skipping this cycle",
+                collector.name()
+            );
+        } else {
+            pending = run_once(collector.clone(), schedule.timeout);
+        }
+
+        if let Some(remaining) = schedule.interval.checked_sub(start.elapsed()) {
+            thread::sleep(remaining);
+        }
+    }
+}
+
+// Run a single collection, waiting no longer than the requested timeout for it to finish.
+//
+// If the collection times out, the channel which will eventually receive its result is returned
+// so that the caller can tell when the collector is no longer hung
+pub fn run_once(
+    collector: Arc<dyn Collector>,
+    timeout: Duration,
+) -> Option<Receiver<Result<(), Error>>> {
+    let name = collector.name().to_owned();
+    let (sender, receiver) = mpsc::channel();
+
+    let spawned = thread::Builder::new()
+        .name(format!("{}-collector", name))
+        .spawn(move || {
+            let _ = sender.send(collector::collect(collector.as_ref()));
+        });
+
+    if let Err(error) = spawned {
+        error!("Failed to start {} telemetry collection: {:?}", name, error);
+        return None;
+    }
+
+    match receiver.recv_timeout(timeout) {
+        Ok(Ok(())) => None,
+        Ok(Err(error)) => {
+            error!("Error while fetching {} telemetry: {:?}", name, error);
+            None
+        }
+        Err(RecvTimeoutError::Timeout) => {
+            error!(
+                "{} telemetry collection hung. No response after {:?}",
+                name, timeout
+            );
+            stats::record_timeout(&name);
+            Some(receiver)
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+            error!("{} telemetry collection panicked", name);
+            None
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// On-disk holding area for telemetry points which couldn't be delivered to the telemetry
+// service
+//
+// Points are stored one JSON message per line, oldest first, so that they can be replayed in
+// the order they were collected. The spool is bounded: once it is full, the oldest points are
+// dropped to make room for new ones.
+//
+// Every collector thread, along with any on-demand runs of the app, shares the same spool, so
+// anything which reads and rewrites the spool must hold its lock (see `Spool::lock`) while it
+// does. The lock is only for the file operations themselves, and is never held while talking to
+// the telemetry service.
+//
+// Only one sender replays spooled points at a time (see `Spool::try_replay`), so that the same
+// points never get sent twice.
+
+use failure::{bail, Error};
+use kubos_app::ServiceConfig;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+const DEFAULT_PATH: &str = "/home/system/kubos/telem-app-spool";
+// Roughly 10 full collection cycles worth of telemetry
+const DEFAULT_CAPACITY: usize = 10_000;
+// Number of spooled points to replay at once, so that a large backlog doesn't hold up the
+// collector which happened to find the telemetry service alive again
+const DEFAULT_REPLAY: usize = 500;
+
+pub struct Spool {
+    pub path: String,
+    pub capacity: usize,
+    pub replay: usize,
+}
+
+// Exclusive access to the spool. The lock is released when this is dropped
+pub struct SpoolLock {
+    _file: File,
+}
+
+impl Spool {
+    // Get the spool settings from the `[telem-app]` section of the config file:
+    //
+    // [telem-app]
+    // spool_path = "/home/system/kubos/telem-app-spool"
+    // spool_capacity = 10000
+    // spool_replay = 500
+    pub fn new(config: &ServiceConfig) -> Spool {
+        let path = config
+            .get("spool_path")
+            .and_then(|val| val.as_str().map(|path| path.to_owned()))
+            .unwrap_or_else(|| DEFAULT_PATH.to_owned());
+
+        let capacity = config
+            .get("spool_capacity")
+            .and_then(|val| val.as_integer())
+            .map(|val| val as usize)
+            .unwrap_or(DEFAULT_CAPACITY);
+
+        let replay = config
+            .get("spool_replay")
+            .and_then(|val| val.as_integer())
+            .map(|val| val as usize)
+            .unwrap_or(DEFAULT_REPLAY);
+
+        Spool {
+            path,
+            capacity,
+            replay,
+        }
+    }
+
+    // Wait for exclusive access to the spool.
+    // The spool itself is replaced whenever it's rewritten, so the lock is taken on a separate
+    // `<path>.lock` file, which is never removed. Each call opens the file separately, so this
+    // keeps threads within the same process apart as well
+    pub fn lock(&self) -> Result<SpoolLock, Error> {
+        let file = flock(&format!("{}.lock", self.path), libc::LOCK_EX)?;
+        Ok(SpoolLock { _file: file })
+    }
+
+    // Claim the right to replay spooled points, if nobody else currently has it.
+    // Returns `None` straight away if another sender is already replaying
+    pub fn try_replay(&self) -> Option<SpoolLock> {
+        flock(
+            &format!("{}.replay", self.path),
+            libc::LOCK_EX | libc::LOCK_NB,
+        )
+        .ok()
+        .map(|file| SpoolLock { _file: file })
+    }
+
+    // Read all of the currently spooled points, oldest first
+    pub fn read(&self) -> Result<Vec<serde_json::Value>, Error> {
+        if !Path::new(&self.path).exists() {
+            return Ok(vec![]);
+        }
+
+        let file = File::open(&self.path)?;
+        let mut points = vec![];
+        for line in BufReader::new(file).lines() {
+            // A partially-written line (ex. from a power cycle in the middle of a write) isn't
+            // worth throwing away the rest of the spool over
+            if let Ok(point) = serde_json::from_str(&line?) {
+                points.push(point);
+            }
+        }
+
+        Ok(points)
+    }
+
+    // Number of points currently waiting in the spool
+    pub fn depth(&self) -> usize {
+        File::open(&self.path)
+            .map(|file| BufReader::new(file).lines().count())
+            .unwrap_or(0)
+    }
+
+    // Add points to the end of the spool.
+    // Returns the number of old points which had to be dropped to make room
+    pub fn append(&self, points: &[serde_json::Value]) -> Result<usize, Error> {
+        {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            for point in points {
+                writeln!(file, "{}", point)?;
+            }
+            file.sync_all()?;
+        }
+
+        if self.depth() > self.capacity {
+            let spooled = self.read()?;
+            self.write(&spooled)
+        } else {
+            Ok(0)
+        }
+    }
+
+    // Replace the contents of the spool.
+    // Returns the number of old points which had to be dropped to stay within the capacity
+    pub fn write(&self, points: &[serde_json::Value]) -> Result<usize, Error> {
+        if points.is_empty() {
+            self.clear()?;
+            return Ok(0);
+        }
+
+        let dropped = points.len().saturating_sub(self.capacity);
+
+        // Write to a temporary file first, so we never end up with a half-written spool
+        let temp_path = format!("{}.tmp", self.path);
+        {
+            let mut file = File::create(&temp_path)?;
+            for point in &points[dropped..] {
+                writeln!(file, "{}", point)?;
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&temp_path, &self.path)?;
+
+        Ok(dropped)
+    }
+
+    pub fn clear(&self) -> Result<(), Error> {
+        if Path::new(&self.path).exists() {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+fn flock(path: &str, operation: libc::c_int) -> Result<File, Error> {
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(path)?;
+
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } != 0 {
+        bail!("Failed to lock {}: {}", path, io::Error::last_os_error());
+    }
+
+    Ok(file)
+}
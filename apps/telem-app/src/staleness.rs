@@ -0,0 +1,129 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Stale and frozen telemetry detection
+//
+// Hardware which has stopped responding properly often keeps answering with the same values
+// over and over, rather than failing outright. After each collection, we check whether:
+//
+// - Any of the subsystem's counters or timestamps (see `Collector::counters`) have stopped
+//   advancing
+// - The whole set of collected values is identical to the previous collection's
+//
+// If either is true for `stale_cycles` collections in a row, the subsystem is marked as stale
+// and an event is raised. Once its telemetry starts changing again, a recovery event is raised.
+// Each collection also records the subsystem's current state as its `stale` parameter.
+//
+// [telem-app]
+// stale_cycles = 3
+
+use crate::events::event;
+use crate::point::{TelemPoint, TelemValue};
+use kubos_app::ServiceConfig;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+
+const DEFAULT_STALE_CYCLES: u32 = 3;
+
+#[derive(Default)]
+struct State {
+    // Everything from the last collection, keyed by parameter
+    last: BTreeMap<String, TelemValue>,
+    // Number of collections in a row which looked frozen
+    frozen: u32,
+    stale: bool,
+}
+
+// Keyed by subsystem
+static STATES: Mutex<BTreeMap<String, State>> = Mutex::new(BTreeMap::new());
+
+// Check a freshly collected set of points.
+// Returns the subsystem's `stale` flag, along with any events which should be sent
+pub fn check(
+    config: &ServiceConfig,
+    subsystem: &str,
+    counters: &[&str],
+    telem_vec: &[TelemPoint],
+) -> (TelemPoint, Vec<TelemPoint>) {
+    let stale_cycles = config
+        .get("stale_cycles")
+        .and_then(|val| val.as_integer())
+        .map(|val| val.max(1) as u32)
+        .unwrap_or(DEFAULT_STALE_CYCLES);
+
+    let timestamp = telem_vec
+        .iter()
+        .map(|point| point.timestamp)
+        .fold(0.0, f64::max);
+
+    let mut states = STATES.lock().unwrap_or_else(PoisonError::into_inner);
+    let state = states.entry(subsystem.to_owned()).or_default();
+
+    // Counters which haven't changed since the last collection
+    let stopped: Vec<&TelemPoint> = telem_vec
+        .iter()
+        .filter(|point| counters.iter().any(|counter| point.parameter == *counter))
+        .filter(|point| state.last.get(&point.parameter) == Some(&point.value))
+        .collect();
+
+    let current: BTreeMap<String, TelemValue> = telem_vec
+        .iter()
+        .map(|point| (point.parameter.clone(), point.value.clone()))
+        .collect();
+    let repeated = !state.last.is_empty() && current == state.last;
+
+    let reason = if let Some(point) = stopped.first() {
+        Some(format!("{} stopped at {}", point.parameter, point.value))
+    } else if repeated {
+        Some(format!("all {} parameters unchanged", current.len()))
+    } else {
+        None
+    };
+
+    state.last = current;
+
+    let mut events = vec![];
+
+    match reason {
+        Some(reason) => {
+            state.frozen = state.frozen.saturating_add(1);
+            if !state.stale && state.frozen >= stale_cycles {
+                state.stale = true;
+                events.push(event(
+                    "telemetry_stale",
+                    format!(
+                        "{} telemetry stale for {} collections: {}",
+                        subsystem, state.frozen, reason
+                    ),
+                    timestamp,
+                ));
+            }
+        }
+        None => {
+            state.frozen = 0;
+            if state.stale {
+                state.stale = false;
+                events.push(event(
+                    "telemetry_fresh",
+                    format!("{} telemetry updating again", subsystem),
+                    timestamp,
+                ));
+            }
+        }
+    }
+
+    (TelemPoint::new("stale", state.stale, timestamp), events)
+}
@@ -0,0 +1,104 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Health of the collection pipeline itself
+//
+// After every collection, each collector reports how it went under the `TELEM_APP` subsystem,
+// so that a gap in a subsystem's telemetry can be told apart from telem-app itself stalling:
+//
+// - `<name>_duration` - Seconds the last collection took
+// - `<name>_points` - Number of points the last collection produced
+// - `<name>_errors` - Number of failed collections since the app started
+// - `<name>_timeouts` - Number of hung collections since the app started
+// - `<name>_last_success` - Time of the last successful collection
+
+use crate::point::TelemPoint;
+use crate::telem_db::{send_telem, timestamp_now, TELEM_APP_SUBSYSTEM};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+#[derive(Default)]
+struct Stats {
+    errors: u64,
+    timeouts: u64,
+    last_success: Option<f64>,
+}
+
+// Keyed by collector name
+static STATS: Mutex<BTreeMap<String, Stats>> = Mutex::new(BTreeMap::new());
+
+// Record the results of a finished collection
+pub fn record_collection(name: &str, duration: Duration, points: usize, success: bool) {
+    let timestamp = timestamp_now();
+
+    let telem_vec = {
+        let mut all_stats = STATS.lock().unwrap_or_else(PoisonError::into_inner);
+        let stats = all_stats.entry(name.to_owned()).or_default();
+
+        if success {
+            stats.last_success = Some(timestamp);
+        } else {
+            stats.errors += 1;
+        }
+
+        let mut telem_vec = vec![
+            TelemPoint::new(
+                format!("{}_duration", name),
+                duration.as_secs_f64(),
+                timestamp,
+            ),
+            TelemPoint::new(format!("{}_points", name), points as u64, timestamp),
+        ];
+        telem_vec.extend(counters(name, stats, timestamp));
+        telem_vec
+    };
+
+    send_telem(TELEM_APP_SUBSYSTEM, telem_vec);
+}
+
+// Record a collection which didn't finish within its timeout
+pub fn record_timeout(name: &str) {
+    let timestamp = timestamp_now();
+
+    let telem_vec = {
+        let mut all_stats = STATS.lock().unwrap_or_else(PoisonError::into_inner);
+        let stats = all_stats.entry(name.to_owned()).or_default();
+
+        stats.timeouts += 1;
+
+        counters(name, stats, timestamp)
+    };
+
+    send_telem(TELEM_APP_SUBSYSTEM, telem_vec);
+}
+
+fn counters(name: &str, stats: &Stats, timestamp: f64) -> Vec<TelemPoint> {
+    let mut telem_vec = vec![
+        TelemPoint::new(format!("{}_errors", name), stats.errors, timestamp),
+        TelemPoint::new(format!("{}_timeouts", name), stats.timeouts, timestamp),
+    ];
+
+    if let Some(last_success) = stats.last_success {
+        telem_vec.push(TelemPoint::new(
+            format!("{}_last_success", name),
+            last_success,
+            timestamp,
+        ));
+    }
+
+    telem_vec
+}
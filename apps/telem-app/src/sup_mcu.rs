@@ -15,43 +15,226 @@
 //
 
 // Gather telemetry from the Pumpkin supervisor MCUs
+//
+// We collect from every module listed in the `[pumpkin-mcu-service.modules]` section of the
+// config file. By default, every field of a module is fetched each time it's collected. Since
+// each field takes ~200ms to fetch, a module's collection can instead be limited to a list of
+// fields, each with its own sampling interval in seconds:
+//
+// [telem-app.bm2.fields]
+// voltage = 60
+// current = 60
+// firmware_version = 0 # Only fetched once per boot
+//
+// Fields are only checked when their module is collected, so a field's interval is effectively
+// rounded up to a multiple of the module's own collection interval.
 
-use crate::telem_db::send_telem;
+use crate::collector::Collector;
+use crate::point::{TelemPoint, TelemValue};
+use crate::schedule::Schedule;
+use crate::telem_db::timestamp_now;
 use failure::Error;
 use kubos_app::*;
-use std::time::Duration;
+use log::*;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+// The modules all share the same I2C bus, so we only talk to one of them at a time. Otherwise a
+// module waiting for its turn could time out before it's even been asked for anything
+static SUP_MCU_BUS: Mutex<()> = Mutex::new(());
+
+// When each allow-listed field was last fetched, keyed by (module, field)
+static LAST_FETCHED: Mutex<BTreeMap<(String, String), Instant>> = Mutex::new(BTreeMap::new());
+
+// Get the names of all of the Sup MCU modules present in the system
+pub fn modules() -> Vec<String> {
+    let modules: Vec<String> = ServiceConfig::new("pumpkin-mcu-service")
+        .get("modules")
+        .and_then(|modules| {
+            modules
+                .as_table()
+                .map(|table| table.keys().cloned().collect())
+        })
+        .unwrap_or_default();
+
+    if modules.is_empty() {
+        warn!("No Sup MCU modules found in [pumpkin-mcu-service.modules]");
+    }
+
+    modules
+}
+
+// Each of the Sup MCU modules is collected separately, and stored under its own subsystem
+pub struct SupMcu {
+    pub module: String,
+}
+
+impl SupMcu {
+    // Get the module's field allow-list, if it has one, as (field, interval) pairs.
+    // An interval of 0 means the field should only be fetched once
+    fn allowed_fields(&self) -> Option<Vec<(String, Option<Duration>)>> {
+        let section = ServiceConfig::new("telem-app").get(&self.module)?;
+        let fields = section.get("fields")?.as_table()?;
+
+        Some(
+            fields
+                .iter()
+                .map(|(field, interval)| {
+                    let interval = interval.as_integer().unwrap_or(0);
+                    let interval = if interval > 0 {
+                        Some(Duration::from_secs(interval as u64))
+                    } else {
+                        None
+                    };
+                    (field.to_owned(), interval)
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Collector for SupMcu {
+    fn name(&self) -> &str {
+        &self.module
+    }
+
+    fn subsystem(&self) -> &str {
+        &self.module
+    }
+
+    // It can take up to 20 seconds to fetch a module's telemetry, but we might also have to
+    // wait for all of the other modules to finish first
+    fn default_schedule(&self) -> Schedule {
+        Schedule::new(60, 150)
+    }
 
-pub fn get_telem() -> Result<(), Error> {
-    let service = ServiceConfig::new("pumpkin-mcu-service");
+    fn counters(&self) -> &[&str] {
+        &["time"]
+    }
+
+    fn query(&self, telem_vec: &mut Vec<TelemPoint>) -> Result<(), Error> {
+        let service = ServiceConfig::new("pumpkin-mcu-service");
+
+        // Figure out which fields are due to be fetched
+        let (request, fields, timeout) = match self.allowed_fields() {
+            None => (
+                format!("{{mcuTelemetry(module: \"{}\")}}", self.module),
+                vec![],
+                // The delay is 200ms per field requested.
+                // Each module will have ~20 fields
+                Duration::from_secs(20),
+            ),
+            Some(allowed) => {
+                let last_fetched = LAST_FETCHED.lock().unwrap_or_else(PoisonError::into_inner);
+                let due: Vec<String> = allowed
+                    .into_iter()
+                    .filter(|(field, interval)| {
+                        match last_fetched.get(&(self.module.clone(), field.clone())) {
+                            None => true,
+                            Some(last) => interval
+                                .map(|interval| last.elapsed() >= interval)
+                                .unwrap_or(false),
+                        }
+                    })
+                    .map(|(field, _)| field)
+                    .collect();
 
-    // Get all the telemetry for all the Sup MCU modules we have
-    let modules = ["aim2", "bim", "pim", "sim", "rhm", "bm2"];
+                if due.is_empty() {
+                    return Ok(());
+                }
+
+                let list: Vec<String> = due.iter().map(|field| format!("\"{}\"", field)).collect();
+                (
+                    format!(
+                        "{{mcuTelemetry(module: \"{}\", fields: [{}])}}",
+                        self.module,
+                        list.join(", ")
+                    ),
+                    due.clone(),
+                    // Leave plenty of room over the expected 200ms per field
+                    Duration::from_secs(due.len().max(2) as u64),
+                )
+            }
+        };
 
-    for module in modules.iter() {
-        let result = query(
-            &service,
-            &format!("{{mcuTelemetry(module: \"{}\")}}", module),
-            // The delay is 200ms per field requested.
-            // Each module will have ~20 fields
-            Some(Duration::from_secs(20)),
-        )?;
+        let _bus = SUP_MCU_BUS.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let timestamp = timestamp_now();
+        let result = query(&service, &request, Some(timeout))?;
 
         let telem_raw = result["mcuTelemetry"].as_str().unwrap_or("");
         let telem: serde_json::Value = serde_json::from_str(telem_raw)?;
 
-        let mut telem_vec: Vec<(String, String)> = vec![];
-        if let Some(inner) = telem.as_object() {
-            for (key, value) in inner.iter() {
-                if value["timestamp"] != 0 {
-                    let data = &value["data"];
+        let points = read_fields(&telem, timestamp);
+        record_fetched(&self.module, &fields, &points);
+        telem_vec.extend(points);
+
+        Ok(())
+    }
+}
+
+// Convert the module's telemetry into points. Fields the module couldn't read are reported
+// with a timestamp of 0, and are skipped
+fn read_fields(telem: &serde_json::Value, timestamp: f64) -> Vec<TelemPoint> {
+    let mut points = vec![];
 
-                    telem_vec.push((key.to_owned(), format!("{}", data)));
+    if let Some(inner) = telem.as_object() {
+        for (key, value) in inner.iter() {
+            if value["timestamp"] != 0 {
+                if let Some(data) = TelemValue::from_json(&value["data"]) {
+                    points.push(TelemPoint::new(key.as_str(), data, timestamp));
                 }
             }
         }
+    }
+
+    points
+}
 
-        send_telem(module, telem_vec);
+// Note when the allow-listed fields were fetched. Fields which didn't come back with a value
+// aren't counted, so that they're tried again the next time the module is collected
+fn record_fetched(module: &str, fields: &[String], points: &[TelemPoint]) {
+    let mut last_fetched = LAST_FETCHED.lock().unwrap_or_else(PoisonError::into_inner);
+    let now = Instant::now();
+
+    for field in fields {
+        if points.iter().any(|point| &point.parameter == field) {
+            last_fetched.insert((module.to_owned(), field.clone()), now);
+        }
     }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn only_fields_with_values_count_as_fetched() {
+        let telem = json!({
+            "voltage": { "data": 8243, "timestamp": 1554134400.0 },
+            "current": { "data": -120, "timestamp": 0 },
+            "firmware_version": { "data": null, "timestamp": 1554134400.0 }
+        });
+        let points = read_fields(&telem, 100.0);
+
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].parameter, "voltage");
+        assert_eq!(points[0].value, TelemValue::Int(8243));
+        assert_eq!(points[0].timestamp, 100.0);
+
+        let fields: Vec<String> = ["voltage", "current", "firmware_version"]
+            .iter()
+            .map(|field| field.to_string())
+            .collect();
+        record_fetched("test_module", &fields, &points);
+
+        let last_fetched = LAST_FETCHED.lock().unwrap_or_else(PoisonError::into_inner);
+        let fetched =
+            |field: &str| last_fetched.contains_key(&("test_module".to_owned(), field.to_owned()));
+        assert!(fetched("voltage"));
+        assert!(!fetched("current"));
+        assert!(!fetched("firmware_version"));
+    }
 }
@@ -16,47 +16,326 @@
 
 // Helper functions to breakup and store data returned from the subsystems
 
+use crate::derived::{self, DERIVED_SUBSYSTEM};
+use crate::events::EVENT_SUBSYSTEM;
+use crate::limits;
+use crate::point::{TelemPoint, TelemValue};
+use crate::spool::{Spool, SpoolLock};
+use crate::units;
+use failure::{bail, Error};
 use kubos_app::*;
-use serde_json::{json, ser};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
-use std::thread;
-use std::time::Duration;
+use log::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-// Send a list of key/value pairs to the telemetry database via the direct UDP port
-pub fn send_telem(subsystem: &str, telem_vec: Vec<(String, String)>) {
-    let config = ServiceConfig::new("telemetry-service");
+// Subsystem used for telemetry about telem-app itself
+pub const TELEM_APP_SUBSYSTEM: &str = "TELEM_APP";
 
-    let port = config
-        .get("direct_port")
-        .expect("No `direct_port` param given");;
+// Running count of how many points have been dropped because the spool was full
+static SPOOL_DROPPED: AtomicU64 = AtomicU64::new(0);
 
-    let host = config.hosturl().to_owned();
-    let ip: Vec<&str> = host.split(':').collect();
+// Get the current system time, in the same format the telemetry database uses for timestamps.
+// Collectors should call this right before querying their subsystem, so that each point is
+// stored with the time it was measured, rather than the time it was inserted into the database
+pub fn timestamp_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+// Send a list of telemetry points to the telemetry database
+//
+// Points are inserted in batches using the telemetry service's `insertBulk` mutation, rather than
+// one at a time. Each batch is kept under `batch_max_bytes` (see `Batcher`), so a large
+// collection (ex. the ~100 EPS parameters) is automatically split into multiple requests.
+//
+// We ping the telemetry service before sending anything. If it doesn't answer (ex. it's
+// restarting), or if a batch isn't acknowledged, the points are stored in the spool instead, and
+// are replayed in order once the service is back. The spool is only locked while it's being read
+// or rewritten, so collectors never have to wait on each other's requests to the service.
+//
+// Before anything is sent, any derived parameters which depend on the points are computed (see
+// `derived`), everything is checked against its configured limits (see `limits`), and the
+// results are sent along with the points.
+pub fn send_telem(subsystem: &str, telem_vec: Vec<TelemPoint>) {
+    send_telem_with(&ServiceConfig::new("telem-app"), subsystem, telem_vec);
+}
+
+// The same as `send_telem`, using an already loaded copy of the app's config, so that a
+// collection which sends several sets of points only has to read it once
+pub fn send_telem_with(config: &ServiceConfig, subsystem: &str, mut telem_vec: Vec<TelemPoint>) {
+    for point in telem_vec.iter_mut() {
+        if point.unit.is_none() {
+            point.unit = units::lookup(subsystem, &point.parameter);
+        }
+    }
+
+    let derived = derived::update(config, subsystem, &telem_vec);
 
-    let remote_addr = format!("{}:{}", ip[0], port);
+    let mut events = limits::check(config, subsystem, &telem_vec);
+    events.extend(limits::check(config, DERIVED_SUBSYSTEM, &derived));
 
-    let local_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0);
+    let messages: Vec<serde_json::Value> = telem_vec
+        .iter()
+        .map(|point| point.to_message(subsystem))
+        .chain(
+            derived
+                .iter()
+                .map(|point| point.to_message(DERIVED_SUBSYSTEM)),
+        )
+        .chain(events.iter().map(|event| event.to_message(EVENT_SUBSYSTEM)))
+        .chain(telem_vec.iter().flat_map(|point| point.describe(subsystem)))
+        .chain(
+            derived
+                .iter()
+                .flat_map(|point| point.describe(DERIVED_SUBSYSTEM)),
+        )
+        .collect();
 
-    let socket = UdpSocket::bind(local_addr).expect("Couldn't bind to address");
+    let spool = Spool::new(config);
 
-    for (key, value) in telem_vec.iter() {
-        let message = json!({
-            "subsystem": subsystem,
-            "parameter": key,
-            "value": value
+    if !service_alive() {
+        warn!(
+            "Telemetry service unavailable. Spooling {} {} points",
+            messages.len(),
+            subsystem
+        );
+        let _lock = lock(&spool);
+        spool_append(&spool, &messages);
+        return;
+    }
+
+    let batcher = Batcher::new(config);
+
+    // Only one sender replays the spool at a time. Anyone else who finds points waiting adds
+    // their own to the back of the spool, so that everything is still delivered in order
+    let replay = spool.try_replay();
+
+    // Replay the oldest spooled points first. If there are still more waiting after that, the
+    // new points go to the back of the spool
+    let (queue, replayed) = {
+        let _lock = lock(&spool);
+        let mut spooled = spool.read().unwrap_or_else(|error| {
+            error!("Failed to read telemetry spool: {:?}", error);
+            vec![]
         });
 
-        socket
-            .send_to(&ser::to_vec(&message).unwrap(), &remote_addr)
-            .unwrap();
+        if spooled.is_empty() {
+            (messages, 0)
+        } else {
+            let waiting = spooled.len();
+            let replayed = match replay {
+                Some(_) => waiting.min(spool.replay),
+                None => 0,
+            };
+            spooled.truncate(replayed);
+
+            if replayed == waiting {
+                spooled.extend(messages);
+            } else {
+                spool_append(&spool, &messages);
+            }
+
+            (spooled, replayed)
+        }
+    };
+
+    let sent = batcher.send(&queue);
 
-        // Give the telemetry service just a little bit of breathing room
-        thread::sleep(Duration::from_millis(1));
+    // The replayed points which got through can now come out of the spool. Any new points which
+    // didn't get through go in behind the replayed points which are still waiting, which keeps
+    // them ahead of anything spooled in the meantime
+    if replayed > 0 || sent < queue.len() {
+        let unsent = &queue[sent.max(replayed)..];
+        let _lock = lock(&spool);
+
+        match spool.read() {
+            Ok(mut spooled) => {
+                let done = sent.min(replayed).min(spooled.len());
+                spooled.drain(..done);
+                let behind = (replayed - sent.min(replayed)).min(spooled.len());
+                spooled.splice(behind..behind, unsent.iter().cloned());
+
+                match spool.write(&spooled) {
+                    Ok(count) => record_dropped(count),
+                    Err(error) => error!("Failed to update telemetry spool: {:?}", error),
+                }
+            }
+            Err(error) => {
+                error!("Failed to read telemetry spool: {:?}", error);
+                spool_append(&spool, unsent);
+            }
+        }
+    }
+
+    drop(replay);
+
+    // Record how the spool is doing. If the telemetry service is down, there's nothing we can
+    // do with these, but the dropped count is a running total, so it'll catch up on the next
+    // successful delivery
+    let timestamp = timestamp_now();
+    let stats = [
+        TelemPoint::new("spool_depth", spool.depth() as u64, timestamp)
+            .to_message(TELEM_APP_SUBSYSTEM),
+        TelemPoint::new(
+            "spool_dropped",
+            SPOOL_DROPPED.load(Ordering::Relaxed),
+            timestamp,
+        )
+        .to_message(TELEM_APP_SUBSYSTEM),
+    ];
+    batcher.send(&stats);
+}
+
+// If we can't get the spool's lock, it's still better to risk a clash than to lose the points
+fn lock(spool: &Spool) -> Option<SpoolLock> {
+    spool
+        .lock()
+        .map_err(|error| error!("Failed to lock telemetry spool: {:?}", error))
+        .ok()
+}
+
+fn spool_append(spool: &Spool, messages: &[serde_json::Value]) {
+    if messages.is_empty() {
+        return;
+    }
+
+    match spool.append(messages) {
+        Ok(count) => record_dropped(count),
+        Err(error) => {
+            error!("Failed to spool telemetry: {:?}", error);
+            record_dropped(messages.len());
+        }
+    }
+}
+
+fn record_dropped(count: usize) {
+    SPOOL_DROPPED.fetch_add(count as u64, Ordering::Relaxed);
+}
+
+// Ask the telemetry service whether it's up and running
+fn service_alive() -> bool {
+    let service = ServiceConfig::new("telemetry-service");
+
+    match query(&service, "{ping}", Some(Duration::from_millis(200))) {
+        Ok(data) => data["ping"] == "pong",
+        Err(_) => false,
+    }
+}
+
+// Largest insert request we'll send, in bytes. This keeps us well under the maximum UDP
+// datagram size, and limits how much has to be re-sent if a request fails
+const DEFAULT_BATCH_MAX_BYTES: usize = 4096;
+
+// Packs telemetry messages into as few `insertBulk` requests as possible
+struct Batcher {
+    service: ServiceConfig,
+    max_bytes: usize,
+}
+
+impl Batcher {
+    // The batch size can be changed in the `[telem-app]` section of the config file:
+    //
+    // [telem-app]
+    // batch_max_bytes = 4096
+    fn new(config: &ServiceConfig) -> Batcher {
+        let max_bytes = config
+            .get("batch_max_bytes")
+            .and_then(|val| val.as_integer())
+            .map(|val| val as usize)
+            .unwrap_or(DEFAULT_BATCH_MAX_BYTES);
+
+        Batcher {
+            service: ServiceConfig::new("telemetry-service"),
+            max_bytes,
+        }
+    }
+
+    // Send the messages, in order.
+    // Returns the number of messages which were acknowledged by the telemetry service. We stop
+    // at the first batch which fails, so that the rest can be spooled without anything getting
+    // out of order
+    fn send(&self, messages: &[serde_json::Value]) -> usize {
+        let mut sent = 0;
+
+        for batch in self.split(messages) {
+            if let Err(error) = self.insert(&batch) {
+                error!("Failed to send telemetry: {:?}", error);
+                break;
+            }
+            sent += batch.len();
+        }
+
+        sent
+    }
+
+    // Convert each message into an `insertBulk` entry and group them into batches which are no
+    // larger than the maximum request size. A single entry which is too large on its own still
+    // gets sent in its own batch
+    fn split(&self, messages: &[serde_json::Value]) -> Vec<Vec<String>> {
+        let overhead = Batcher::request(&[]).len();
+        let mut batches = vec![];
+        let mut batch: Vec<String> = vec![];
+        let mut size = overhead;
+
+        for message in messages {
+            let entry = Batcher::entry(message);
+            // Entries are comma-separated
+            if !batch.is_empty() && size + entry.len() + 1 > self.max_bytes {
+                batches.push(batch);
+                batch = vec![];
+                size = overhead;
+            }
+            size += entry.len() + 1;
+            batch.push(entry);
+        }
+
+        if !batch.is_empty() {
+            batches.push(batch);
+        }
+
+        batches
+    }
+
+    fn insert(&self, batch: &[String]) -> Result<(), Error> {
+        let response = query(
+            &self.service,
+            &Batcher::request(batch),
+            Some(Duration::from_secs(2)),
+        )?;
+
+        let data = &response["insertBulk"];
+        if data["success"].as_bool() != Some(true) {
+            bail!("Bulk insert failed: {}", data["errors"]);
+        }
+
+        Ok(())
+    }
+
+    fn request(entries: &[String]) -> String {
+        format!(
+            "mutation {{ insertBulk(entries: [{}]) {{ success, errors }} }}",
+            entries.join(",")
+        )
+    }
+
+    // Strings are written using their JSON representation, which escapes them in a way that is
+    // also valid for GraphQL
+    fn entry(message: &serde_json::Value) -> String {
+        format!(
+            "{{timestamp: {}, subsystem: {}, parameter: {}, value: {}}}",
+            message["timestamp"].as_f64().unwrap_or(0.0),
+            message["subsystem"],
+            message["parameter"],
+            message["value"]
+        )
     }
 }
 
 // Convert an unknown list of telemetry in JSON format into a flat-structured set of
-// key/value pairs.
+// typed telemetry points. Every resulting point is given the same timestamp: the time the
+// subsystem was queried. `null` values are skipped, since there's nothing to store.
 //
 // Example:
 // Input -
@@ -73,29 +352,47 @@ pub fn send_telem(subsystem: &str, telem_vec: Vec<(String, String)>) {
 //
 // Output -
 // [
-//    ("telemetry_power_voltage", "5"),
-//    ("telemetry_power_current", "0.3"),
-//    ("telemetry_status", "Okay"),
-//    ("telemetry_position_0", "1.3"),
-//    ("telemetry_position_1", "-4.5"),
-//    ("telemetry_position_2", "9.0")
+//    ("telemetry_power_voltage", Int(5)),
+//    ("telemetry_power_current", Float(0.3)),
+//    ("telemetry_status", Str("Okay")),
+//    ("telemetry_position_0", Float(1.3)),
+//    ("telemetry_position_1", Float(-4.5)),
+//    ("telemetry_position_2", Float(9.0))
 // ]
 pub fn process_json(
-    mut telem_vec: &mut Vec<(String, String)>,
+    mut telem_vec: &mut Vec<TelemPoint>,
     data: &serde_json::Map<String, serde_json::Value>,
     prefix: String,
+    timestamp: f64,
 ) {
     for (key, value) in data.iter() {
         match value {
-            serde_json::Value::Object(object) => {
-                process_json(&mut telem_vec, object, format!("{}{}_", prefix, key))
-            }
+            serde_json::Value::Object(object) => process_json(
+                &mut telem_vec,
+                object,
+                format!("{}{}_", prefix, key),
+                timestamp,
+            ),
             serde_json::Value::Array(array) => {
                 for (index, val) in array.iter().enumerate() {
-                    telem_vec.push((format!("{}{}_{}", prefix, key, index), format!("{}", val)))
+                    if let Some(val) = TelemValue::from_json(val) {
+                        telem_vec.push(TelemPoint::new(
+                            format!("{}{}_{}", prefix, key, index),
+                            val,
+                            timestamp,
+                        ))
+                    }
+                }
+            }
+            _ => {
+                if let Some(val) = TelemValue::from_json(value) {
+                    telem_vec.push(TelemPoint::new(
+                        format!("{}{}", prefix, key),
+                        val,
+                        timestamp,
+                    ))
                 }
             }
-            _ => telem_vec.push((format!("{}{}", prefix, key), format!("{}", value))),
         }
     }
 }
@@ -0,0 +1,87 @@
+//
+// Copyright (C) 2019 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Engineering units for the telemetry we collect
+//
+// Each entry is (subsystem, parameter fragment, unit). The first entry whose subsystem matches
+// and whose fragment appears in the parameter name wins, so more specific fragments need to come
+// before more general ones. A fragment starting with `^` only matches at the start of the name.
+
+const UNITS: &[(&str, &str, &str)] = &[
+    // Clyde Space EPS
+    ("EPS", "VoltageFeedingBcr", "V"),
+    ("EPS", "CurrentBcr", "mA"),
+    ("EPS", "ArrayTemp", "degC"),
+    ("EPS", "BoardTemperature", "degC"),
+    ("EPS", "BcrOutputVoltage", "V"),
+    ("EPS", "BcrOutputCurrent", "mA"),
+    ("EPS", "CurrentDraw", "mA"),
+    ("EPS", "OutputVoltage", "V"),
+    ("EPS", "OutputCurrent", "mA"),
+    // BM2 battery module
+    ("bm2", "ts1_temp", "0.1degC"),
+    ("bm2", "ts2_temp", "0.1degC"),
+    ("bm2", "temperature", "0.1K"),
+    ("bm2", "capacity", "mAh"),
+    ("bm2", "voltage", "mV"),
+    ("bm2", "current", "mA"),
+    // BIM temperature sensors
+    ("bim", "temp", "K"),
+    // AIM2 GPS power monitoring
+    ("aim2", "oem_power", "W"),
+    // MBM2 memory usage, as reported by /proc/meminfo
+    ("OBC", "^memory_", "kB"),
+    // MBM2 system info, from /proc and /sys
+    ("OBC", "_pct", "%"),
+    ("OBC", "disk_", "kB"),
+    ("OBC", "_rss", "kB"),
+    ("OBC", "uptime", "s"),
+    ("OBC", "soc_temp", "degC"),
+    // NovAtel OEM
+    ("OEM", "position_", "m"),
+    ("OEM", "velocity_", "m/s"),
+    ("OEM", "time_ms", "ms"),
+    ("OEM", "fix_age", "s"),
+    // GPS times converted to Unix time
+    ("OEM", "_utc", "s"),
+    ("MAI400", "_utc", "s"),
+    // Orbit propagation
+    ("ORBIT", "position", "m"),
+    ("ORBIT", "velocity", "m/s"),
+    ("ORBIT", "latitude", "deg"),
+    ("ORBIT", "longitude", "deg"),
+    ("ORBIT", "altitude", "m"),
+    ("ORBIT", "state_age", "s"),
+    // GPS clock sync
+    ("CLOCK", "correction", "s"),
+    ("CLOCK", "uncertainty", "s"),
+    // Collection pipeline health
+    ("TELEM_APP", "_duration", "s"),
+];
+
+// Look up the engineering unit for a parameter
+pub fn lookup(subsystem: &str, parameter: &str) -> Option<&'static str> {
+    UNITS
+        .iter()
+        .find(|(sys, fragment, _)| {
+            *sys == subsystem
+                && match fragment.strip_prefix('^') {
+                    Some(prefix) => parameter.starts_with(prefix),
+                    None => parameter.contains(fragment),
+                }
+        })
+        .map(|(_, _, unit)| *unit)
+}
@@ -57,7 +57,12 @@ class Satellite:
             await self.major_tom.complete_command(command_id=sp.command_id,
                                             output=sp.payload)
         elif sp.type == 0:
-            logger.warning("UDP type is not currently supported.")
+            # Beacons from beacon-app come down through the comms service's downlink port
+            logger.info(f'Beacon received on port {sp.port}: {sp.payload.hex()}')
+            await self.major_tom.transmit_log_messages([{
+                "system": self.system_name,
+                "message": f'Beacon: {sp.payload.hex()}'
+            }])
 
         # TODO: graphql validation
         # # {'errs': '', 'msg': { errs: '..' }}
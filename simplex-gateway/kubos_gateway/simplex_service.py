@@ -10,40 +10,37 @@ import asyncio
 import binascii
 import json
 import logging
+import os
 from kubos_gateway.nsl_simplex_webapi import NSLWeb
 import struct
 from kubos_gateway.satellite import Satellite
 
 LOGGER = logging.getLogger(__name__)
 
-# Parsing tables for the H&S beacons
-TEMPERATURE = {"parsing": "20b",
-                "names": ["eps_mb_temp","eps_db_temp","eps_bcr2a_temp","eps_bcr2b_temp","eps_bcr8a_temp","eps_bcr8b_temp","eps_bcr9a_temp","eps_bcr9b_temp","mai_gyro_temp","mai_motor_temp","bim_temp0","bim_temp1","bim_temp2","bim_temp3","bim_temp4","bim_temp5","bm2_temp","bm2_ts1_temp","bm2_ts2_temp","bm2_temp_range"]}
-ADCS1 = {"parsing": "<LHHHBBBBf",
-         "names": ["gps_time","good_cmd_count","bad_cmd_count","bad_checksum_count","last_command","acs_mode","attdet_mode","eclipse","angle_to_go"]}
-ADCS2 = {"parsing": "<3f3h3h4h",
-         "names": ["body_rate_x","body_rate_y","body_rate_z","wheel_speed_x","wheel_speed_y","wheel_speed_z","wheel_bias_x","wheel_bias_y","wheel_bias_z","qbo_0","qbo_1","qbo_2","qbo_3"]}
-APP_ERRORS = {"parsing": "<HH8s22s",
-           "names": ["app_errors_count", "app_last_timestamp", "app_last_source", "app_last_msg"]}
-SERVICE_ERRORS = {"parsing": "<HH8s22s",
-           "names": ["service_errors_count", "service_last_timestamp", "service_last_source", "service_last_msg"]}
-GPS_POSITION = {"parsing": "<BH3d",
-           "names": ["position_status","position_type","position_x","position_y","position_z"]}
-GPS_VELOCITY = {"parsing": "<BH3d",
-           "names": ["velocity_status","velocity_type","velocity_x","velocity_y","velocity_z"]}
-GPS_MISC = {"parsing": "<BHLLHBfBHL",
-           "names": ["time_status","time_week","time_ms","system_status","gps_status","power_status","power_3v_usb","power","lock_time_week","lock_time_ms"]}
-OBC = {"parsing": "<BBB",
-           "names": ["ram_available","disk_in_use","deployed"]}
-GENERAL_POWER = {"parsing": "<HhH3B3BHHHHhhhhhh",
-           "names": ["voltage","current","pf_status","mb_reset_bo","mb_reset_wdt","mb_reset_sw","db_reset_bo","db_reset_wdt","db_reset_sw","remaining_cap","full_cap","charge_voltage","charge_current","voltage_12v","current_12v","voltage_5v","current_5v","voltage_3v","current_3v"]}
-BATTERY_MB_POWER = {"parsing": "<4H3h3h",
-           "names": ["voltage_cell1","voltage_cell2","voltage_cell3","voltage_cell4","voltage_bcr1","current_bcr1a","current_bcr1b","voltage_bcr2","current_bcr2a","current_bcr2b"]}
-DB_POWER = {"parsing": "<12h",
-           "names": ["voltage_bcr6","current_bcr6a","current_bcr6b","voltage_bcr7","current_bcr7a","current_bcr7b","voltage_bcr8","current_bcr8a","current_bcr8b","voltage_bcr9","current_bcr9a","current_bcr9b"]}
-RADIO = {"parsing": "x"}
-SUPMCU = {"parsing": "<BHBHBHBHBHBH",
-           "names": ["aim2_uptime","aim2_reset","bim_uptime","bim_reset","pim_uptime","pim_reset","sim_uptime","sim_reset","rhm_uptime","rhm_reset","bm2_uptime","bm2_reset"]}
+# Parsing tables for the H&S beacons, keyed by header byte
+# These are generated from the packet definitions in apps/beacon-schema, which are what the
+# beacons are actually built from. Don't edit beacon_tables.json by hand; regenerate it with:
+#
+#   beacon-decoder --tables > kubos_gateway/beacon_tables.json
+TABLES_PATH = os.path.join(os.path.dirname(__file__), "beacon_tables.json")
+
+def load_tables(path=TABLES_PATH):
+    with open(path) as tables:
+        return {packet["header"]: packet for packet in json.load(tables)["packets"]}
+
+PACKETS = load_tables()
+
+# Which subsystem each beacon type belongs to
+SUBSYSTEMS = {
+    "ADCS": "MAI-400",
+    "Errors": "Errors",
+    "GPS": "OEM7",
+    "OBC": "OBC",
+    "Power": "Power",
+    "Radio": "Duplex",
+    "SupMCU": "SupMCU",
+    "Temperature": "Temperature"
+    }
 
 # Temporary dummy data until we can actually send real data over the simplex
 DUMMY_DATA = '''[
@@ -120,23 +117,12 @@ def process_record(data):
 
     packet = binascii.unhexlify(payload[2:])
 
-    (subsystem, input_dict) = {
-        0x01: ("MAI-400", ADCS1),
-        0x02: ("MAI-400", ADCS2),
-        0x09: ("Errors", APP_ERRORS),
-        0x0A: ("Errors", SERVICE_ERRORS),
-        0x11: ("OEM7", GPS_POSITION),
-        0x12: ("OEM7", GPS_VELOCITY),
-        0x13: ("OEM7", GPS_MISC),
-        0x18: ("OBC", OBC),
-        0x21: ("Power", GENERAL_POWER),
-        0x22: ("Power", BATTERY_MB_POWER),
-        0x23: ("Power", DB_POWER),
-        0x28: ("Duplex", RADIO),
-        0x30: ("SupMCU", SUPMCU),
-        0x38: ("Temperature", TEMPERATURE)
-        }.get(header)
-        # TODO: What happens if there's a bad/unknown packet?
+    input_dict = PACKETS.get(header)
+    if input_dict is None:
+        LOGGER.warning("Unknown beacon header: {:#04x}".format(header))
+        return None
+
+    subsystem = SUBSYSTEMS.get(input_dict['type'], input_dict['type'])
 
     # Convert the record into a set of key/value pairs
     output_dict = read_telemetry_items(input_dict, packet)